@@ -0,0 +1,332 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul, Shl, Shr, Sub};
+use std::str::FromStr;
+
+/// Base used when converting to and from decimal, the largest power of ten in a `u32`.
+const DECIMAL_BASE: u32 = 1_000_000_000;
+const DECIMAL_DIGITS: usize = 9;
+
+/// Arbitrary-precision unsigned integer, stored as little-endian 32-bit limbs with no
+/// trailing zero limbs, so zero has no limbs at all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn normalized(mut limbs: Vec<u32>) -> BigUint {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint { limbs }
+    }
+
+    pub fn checked_sub(&self, other: &BigUint) -> Option<BigUint> {
+        if *self < *other {
+            return None;
+        }
+
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0_i64;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let mut diff = *limb as i64 - other.limbs.get(i).copied().unwrap_or(0) as i64 - borrow;
+            borrow = 0;
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+        Some(BigUint::normalized(limbs))
+    }
+
+    fn mul_small_add(&mut self, factor: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in self.limbs.iter_mut() {
+            let product = *limb as u64 * factor as u64 + carry;
+            *limb = product as u32;
+            carry = product >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+        *self = BigUint::normalized(std::mem::take(&mut self.limbs));
+    }
+
+    pub fn div_rem_small(&self, divisor: u32) -> (BigUint, u32) {
+        let mut quotient = vec![0; self.limbs.len()];
+        let mut rem = 0_u64;
+        for (i, limb) in self.limbs.iter().enumerate().rev() {
+            let cur = (rem << 32) | *limb as u64;
+            quotient[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        (BigUint::normalized(quotient), rem as u32)
+    }
+
+    fn bits(&self) -> usize {
+        match self.limbs.last() {
+            None => 0,
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+        }
+    }
+
+    /// Floor of the square root, computed exactly bit by bit.
+    pub fn isqrt(&self) -> BigUint {
+        let mut rem = self.clone();
+        let mut root = BigUint::zero();
+        let mut bit = BigUint::from(1_u32) << (self.bits() / 2 * 2);
+
+        while !bit.is_zero() {
+            let trial = &root + &bit;
+            root = root >> 1;
+            if let Some(r) = rem.checked_sub(&trial) {
+                rem = r;
+                root = &root + &bit;
+            }
+            bit = bit >> 2;
+        }
+
+        root
+    }
+}
+
+impl From<u32> for BigUint {
+    fn from(n: u32) -> BigUint {
+        BigUint::normalized(vec![n])
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(mut n: u128) -> BigUint {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        BigUint { limbs }
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = Box<dyn Error>;
+
+    /// Parses a non-empty string of decimal digits.
+    fn from_str(s: &str) -> Result<BigUint, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("Invalid unsigned integer: {}", s).into());
+        }
+
+        let mut n = BigUint::zero();
+        let head = s.len() % DECIMAL_DIGITS;
+        let chunks = std::iter::once(&s[..head]).filter(|c| !c.is_empty()).chain(
+            s.as_bytes()[head..].chunks(DECIMAL_DIGITS).map(|c| {
+                // Only ASCII digits, checked above.
+                std::str::from_utf8(c).unwrap()
+            }),
+        );
+        for chunk in chunks {
+            let scale = 10_u32.pow(chunk.len() as u32);
+            n.mul_small_add(scale, chunk.parse::<u32>()?);
+        }
+        Ok(n)
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut chunks = Vec::new();
+        let mut n = self.clone();
+        while !n.is_zero() {
+            let (q, r) = n.div_rem_small(DECIMAL_BASE);
+            chunks.push(r);
+            n = q;
+        }
+
+        write!(f, "{}", chunks.pop().unwrap_or(0))?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut limbs = Vec::with_capacity(std::cmp::max(self.limbs.len(), other.limbs.len()) + 1);
+        let mut carry = 0_u64;
+        for i in 0..std::cmp::max(self.limbs.len(), other.limbs.len()) {
+            let sum = self.limbs.get(i).copied().unwrap_or(0) as u64
+                + other.limbs.get(i).copied().unwrap_or(0) as u64
+                + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint::normalized(limbs)
+    }
+}
+
+impl Sub for &BigUint {
+    type Output = BigUint;
+
+    /// Panics if `other` is larger, like unsigned primitive subtraction in debug builds.
+    fn sub(self, other: &BigUint) -> BigUint {
+        self.checked_sub(other)
+            .unwrap_or_else(|| panic!("BigUint subtraction underflow: {} - {}", self, other))
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0_u32; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        BigUint::normalized(limbs)
+    }
+}
+
+impl Shl<usize> for BigUint {
+    type Output = BigUint;
+
+    fn shl(self, bits: usize) -> BigUint {
+        if self.is_zero() {
+            return self;
+        }
+
+        let (whole, part) = (bits / 32, bits % 32);
+        let mut limbs = vec![0_u32; whole];
+        let mut carry = 0_u32;
+        for limb in &self.limbs {
+            limbs.push((limb << part) | carry);
+            carry = if part == 0 { 0 } else { limb >> (32 - part) };
+        }
+        limbs.push(carry);
+        BigUint::normalized(limbs)
+    }
+}
+
+impl Shr<usize> for BigUint {
+    type Output = BigUint;
+
+    fn shr(self, bits: usize) -> BigUint {
+        let (whole, part) = (bits / 32, bits % 32);
+        if whole >= self.limbs.len() {
+            return BigUint::zero();
+        }
+
+        let limbs = &self.limbs[whole..];
+        let shifted = (0..limbs.len())
+            .map(|i| {
+                let high = match limbs.get(i + 1) {
+                    Some(next) if part > 0 => next << (32 - part),
+                    _ => 0,
+                };
+                (limbs[i] >> part) | high
+            })
+            .collect();
+        BigUint::normalized(shifted)
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    fn big(s: &str) -> BigUint {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in [
+            "0",
+            "7",
+            "4294967296",
+            "1000000000",
+            "123456789012345678901234567890",
+        ] {
+            assert_eq!(big(s).to_string(), s);
+        }
+        assert_eq!(big("000123").to_string(), "123");
+        assert_eq!(BigUint::from(u128::MAX).to_string(), u128::MAX.to_string());
+        assert!("12a".parse::<BigUint>().is_err());
+        assert!("".parse::<BigUint>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = BigUint::from(u128::MAX);
+        let one = BigUint::from(1_u32);
+        assert_eq!(
+            (&a + &one).to_string(),
+            "340282366920938463463374607431768211456"
+        );
+        assert_eq!(&(&a + &one) - &one, a);
+        assert_eq!(
+            (&a * &a).to_string(),
+            "115792089237316195423570985008687907852589419931798687112530834793049593217025"
+        );
+        assert_eq!(a.checked_sub(&(&a + &one)), None);
+        assert_eq!((one.clone() << 100) >> 99, BigUint::from(2_u32));
+        assert!(big("99999999999999999999") > big("99999999999999999998"));
+    }
+
+    #[test]
+    fn test_isqrt() {
+        let mut root = 0_u32;
+        for n in 0..2000_u32 {
+            while (root + 1) * (root + 1) <= n {
+                root += 1;
+            }
+            assert_eq!(BigUint::from(n).isqrt(), BigUint::from(root));
+        }
+
+        let root = big(&format!("7{}", "3".repeat(150)));
+        let square = &root * &root;
+        assert_eq!(square.isqrt(), root);
+        let one = BigUint::from(1_u32);
+        assert_eq!((&square - &one).isqrt(), &root - &one);
+    }
+}
@@ -1,64 +1,547 @@
 use std::error::Error;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::io::prelude::*;
 use std::result::Result;
 
-pub fn boat_race(f: File) -> Result<(), Box<dyn Error>> {
-    let reader = BufReader::new(f);
-    let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+use crate::bigint::BigUint;
 
-    let times: Vec<i64> = lines[0]
-        .split_whitespace()
-        .skip(1)
-        .filter_map(|v| v.parse::<i64>().ok())
-        .collect();
+/// One row of the race sheet: each number with its 1-based column, as written.
+struct Row<'a> {
+    line: usize,
+    tokens: Vec<(usize, &'a str)>,
+}
 
-    let distances: Vec<i64> = lines[1]
-        .split_whitespace()
-        .skip(1)
-        .filter_map(|v| v.parse::<i64>().ok())
-        .collect();
+impl<'a> Row<'a> {
+    /// Splits `<header>: <n> <n> ...` into positioned tokens, checking that every one is a
+    /// number.
+    fn parse(line: usize, text: &'a str, header: &str) -> Result<Row<'a>, Box<dyn Error>> {
+        let (label, values) = text
+            .split_once(':')
+            .ok_or(format!("Line {}: expected \"{}:\" header", line, header))?;
+        if label.trim() != header {
+            return Err(format!(
+                "Line {}: expected \"{}:\" header, found \"{}:\"",
+                line,
+                header,
+                label.trim()
+            )
+            .into());
+        }
 
-    let race_specs = times.iter().zip(distances.iter());
-    let p1 = race_specs.fold(1, |acc: i64, new| acc * ways_to_win(*new.0, *new.1));
+        let offset = label.len() + 1;
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in values.char_indices().chain([(values.len(), ' ')]) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(i),
+                (true, Some(s)) => {
+                    tokens.push((offset + s + 1, &values[s..i]));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
 
-    let p2_time = lines[0]
-        .replace("Time: ", "")
-        .replace(" ", "")
-        .parse::<i64>()?;
+        if tokens.is_empty() {
+            return Err(format!("Line {}: no {} values", line, header.to_lowercase()).into());
+        }
+        if let Some((col, token)) = tokens
+            .iter()
+            .find(|(_, t)| !t.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(format!(
+                "Line {}, column {}: invalid {} \"{}\"",
+                line,
+                col,
+                header.to_lowercase(),
+                token
+            )
+            .into());
+        }
 
-    let p2_distance = lines[1]
-        .replace("Distance: ", "")
-        .replace(" ", "")
-        .parse::<i64>()?;
+        Ok(Row { line, tokens })
+    }
 
-    println!("Part 1: {}", p1);
-    println!("Part 2: {}", ways_to_win(p2_time, p2_distance));
+    fn values(&self) -> Result<Vec<BigUint>, Box<dyn Error>> {
+        self.tokens.iter().map(|(_, t)| t.parse()).collect()
+    }
 
-    Ok(())
+    /// The single number read by ignoring the spaces between tokens.
+    fn kerned(&self) -> Result<BigUint, Box<dyn Error>> {
+        self.tokens
+            .iter()
+            .map(|(_, t)| *t)
+            .collect::<String>()
+            .parse()
+    }
+}
+
+/// The parsed race sheet: per-race times and record distances, plus the single race read
+/// with the kerning fixed.
+struct Races {
+    times: Vec<BigUint>,
+    distances: Vec<BigUint>,
+    kerned_time: BigUint,
+    kerned_distance: BigUint,
+}
+
+impl Races {
+    fn parse(input: &str) -> Result<Races, Box<dyn Error>> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l))
+            .filter(|(_, l)| !l.trim().is_empty());
+
+        let (n, text) = lines.next().ok_or("Missing \"Time:\" line")?;
+        let times = Row::parse(n, text, "Time")?;
+        let (n, text) = lines.next().ok_or("Missing \"Distance:\" line")?;
+        let distances = Row::parse(n, text, "Distance")?;
+        if let Some((n, _)) = lines.next() {
+            return Err(format!("Line {}: unexpected input after \"Distance:\" line", n).into());
+        }
+
+        if times.tokens.len() != distances.tokens.len() {
+            return Err(format!(
+                "Line {} has {} times but line {} has {} distances",
+                times.line,
+                times.tokens.len(),
+                distances.line,
+                distances.tokens.len()
+            )
+            .into());
+        }
+
+        Ok(Races {
+            times: times.values()?,
+            distances: distances.values()?,
+            kerned_time: times.kerned()?,
+            kerned_distance: distances.kerned()?,
+        })
+    }
+}
+
+/// How a boat moves. Holding the button for `h` milliseconds gives it a speed of
+/// `initial_speed + acceleration * h`, limited to `speed_cap`, and once released it loses
+/// `drag` speed every millisecond until it stops. The defaults are the puzzle's own rules.
+#[derive(Clone, Debug, PartialEq)]
+struct Physics {
+    acceleration: u32,
+    initial_speed: u32,
+    speed_cap: Option<u32>,
+    drag: u32,
+}
+
+impl Default for Physics {
+    fn default() -> Physics {
+        Physics {
+            acceleration: 1,
+            initial_speed: 0,
+            speed_cap: None,
+            drag: 0,
+        }
+    }
+}
+
+impl Physics {
+    fn parse(args: &[String]) -> Result<Physics, Box<dyn Error>> {
+        let mut physics = Physics::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || -> Result<u32, Box<dyn Error>> {
+                let v = args.next().ok_or(format!("{} requires a number", arg))?;
+                v.parse()
+                    .map_err(|e| format!("Invalid value for {}: {}: {}", arg, v, e).into())
+            };
+            match arg.as_str() {
+                "--acceleration" => physics.acceleration = value()?,
+                "--initial-speed" => physics.initial_speed = value()?,
+                "--speed-cap" => physics.speed_cap = Some(value()?),
+                "--drag" => physics.drag = value()?,
+                x => return Err(format!("Unknown boat race option: {}", x).into()),
+            }
+        }
+
+        Ok(physics)
+    }
+
+    fn speed(&self, hold: &BigUint) -> BigUint {
+        let speed = &BigUint::from(self.initial_speed) + &(hold * &self.acceleration.into());
+        match self.speed_cap {
+            Some(cap) if speed > cap.into() => cap.into(),
+            _ => speed,
+        }
+    }
+
+    /// How far the boat travels in a race of `time` after holding for `hold <= time`.
+    fn distance(&self, time: &BigUint, hold: &BigUint) -> BigUint {
+        let speed = self.speed(hold);
+        let moving = time - hold;
+        if self.drag == 0 {
+            return &speed * &moving;
+        }
+        if speed.is_zero() || moving.is_zero() {
+            return BigUint::zero();
+        }
+
+        // The boat is still moving for the first `ceil(speed / drag)` milliseconds, covering
+        // an arithmetic series of distances.
+        let one = BigUint::from(1_u32);
+        let (stops, _) = (&speed - &one).div_rem_small(self.drag);
+        let steps = std::cmp::min(&stops + &one, moving);
+        let lost = &((&steps * &(&steps - &one)) >> 1) * &self.drag.into();
+        &(&steps * &speed) - &lost
+    }
+
+    /// Counts the hold times in `0..=time` that travel further than `distance`.
+    fn ways_to_win(&self, time: &BigUint, distance: &BigUint) -> BigUint {
+        if self.drag == 0 && self.speed_cap.is_none() && self.acceleration > 0 {
+            self.solve_quadratic(time, distance)
+        } else {
+            self.search(time, distance)
+        }
+    }
+
+    /// Without drag or a cap the distance is `(v + a*h) * (time - h)`, so the winning holds
+    /// lie strictly between the roots of `a*h^2 - (a*time - v)*h + distance - v*time`. Both
+    /// roots are estimated from the exact integer square root of the discriminant
+    /// `(a*time + v)^2 - 4*a*distance` and then nudged onto the true boundaries.
+    fn solve_quadratic(&self, time: &BigUint, distance: &BigUint) -> BigUint {
+        let wins = |h: &BigUint| self.distance(time, h) > *distance;
+        let one = BigUint::from(1_u32);
+        let a = BigUint::from(self.acceleration);
+        let v = BigUint::from(self.initial_speed);
+
+        let a_time = &a * time;
+        let b = &a_time + &v;
+        let discriminant = match (&b * &b).checked_sub(&((&a * distance) << 2)) {
+            Some(d) if !d.is_zero() => d,
+            _ => return BigUint::zero(),
+        };
+        let root = discriminant.isqrt();
+        let over_2a = |n: BigUint| (n >> 1).div_rem_small(self.acceleration).0;
+
+        let mut first = match a_time.checked_sub(&(&v + &root)) {
+            Some(n) => over_2a(n),
+            None => BigUint::zero(),
+        };
+        let mut last = match (&a_time + &root).checked_sub(&v) {
+            Some(n) => std::cmp::min(over_2a(n), time.clone()),
+            None => BigUint::zero(),
+        };
+
+        while !first.is_zero() && wins(&(&first - &one)) {
+            first = &first - &one;
+        }
+        while last < *time && wins(&(&last + &one)) {
+            last = &last + &one;
+        }
+        while first <= last && !wins(&first) {
+            first = &first + &one;
+        }
+        if first > last {
+            return BigUint::zero();
+        }
+        while !wins(&last) {
+            last = &last - &one;
+        }
+
+        &(&last - &first) + &one
+    }
+
+    /// The distance rises with the hold time while the extra speed is worth more than the
+    /// time spent gaining it, then falls or holds at its maximum, so the winning holds form
+    /// one interval around the peak. The peak and both edges are found by binary search,
+    /// evaluating the distance only `O(log time)` times.
+    fn search(&self, time: &BigUint, distance: &BigUint) -> BigUint {
+        let one = BigUint::from(1_u32);
+        let reach = |h: &BigUint| self.distance(time, h);
+
+        let peak = partition_point(BigUint::zero(), time.clone(), |h| {
+            reach(h) < reach(&(h + &one))
+        });
+        if reach(&peak) <= *distance {
+            return BigUint::zero();
+        }
+
+        let first = partition_point(BigUint::zero(), peak.clone(), |h| reach(h) <= *distance);
+        let end = partition_point(peak, time + &one, |h| reach(h) > *distance);
+        &end - &first
+    }
 }
 
-fn ways_to_win(time: i64, distance: i64) -> i64 {
-    let mut ret = 0;
-    for wait in 0..time {
-        let speed = wait;
-        let new_dist = speed * (time - wait);
-        if new_dist > distance {
-            ret += 1;
+/// The first value in `lo..hi` for which `pred` is false, or `hi` if there is none. `pred`
+/// must be true for some prefix of the range and false for the rest.
+fn partition_point<P>(mut lo: BigUint, mut hi: BigUint, pred: P) -> BigUint
+where
+    P: Fn(&BigUint) -> bool,
+{
+    let one = BigUint::from(1_u32);
+    while lo < hi {
+        let mid = (&lo + &hi) >> 1;
+        if pred(&mid) {
+            lo = &mid + &one;
+        } else {
+            hi = mid;
         }
     }
+    lo
+}
+
+pub fn boat_race(mut f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let physics = Physics::parse(args)?;
+
+    let mut input = String::new();
+    f.read_to_string(&mut input)?;
+    let races = Races::parse(&input)?;
+
+    let p1 = races
+        .times
+        .iter()
+        .zip(races.distances.iter())
+        .fold(BigUint::from(1_u32), |acc, (time, distance)| {
+            &acc * &physics.ways_to_win(time, distance)
+        });
 
-    ret
+    println!("Part 1: {}", p1);
+    println!(
+        "Part 2: {}",
+        physics.ways_to_win(&races.kerned_time, &races.kerned_distance)
+    );
+
+    Ok(())
+}
+
+/// Counts the hold times `h` in `0..=time` whose distance `h * (time - h)` beats
+/// `distance`, under the puzzle's own physics.
+#[cfg(test)]
+fn ways_to_win(time: &BigUint, distance: &BigUint) -> BigUint {
+    Physics::default().ways_to_win(time, distance)
 }
 
 mod tests {
     #[cfg(test)]
     use super::*;
 
+    #[cfg(test)]
+    fn brute_force(time: u128, distance: u128) -> u128 {
+        (0..=time).filter(|h| h * (time - h) > distance).count() as u128
+    }
+
+    #[cfg(test)]
+    fn ways(time: u128, distance: u128) -> BigUint {
+        ways_to_win(&BigUint::from(time), &BigUint::from(distance))
+    }
+
+    #[cfg(test)]
+    fn big(s: &str) -> BigUint {
+        s.parse().unwrap()
+    }
+
     #[test]
     fn test_ways_to_win() {
-        let actual = ways_to_win(30, 200);
-        let expected = 9;
+        let actual = ways(30, 200);
+        let expected = BigUint::from(9_u32);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for time in 0..40 {
+            for distance in 0..(time * time / 4 + 3) {
+                assert_eq!(
+                    ways(time, distance),
+                    BigUint::from(brute_force(time, distance)),
+                    "time={} distance={}",
+                    time,
+                    distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_races() {
+        assert_eq!(ways(71530, 940200), BigUint::from(71503_u32));
+
+        // Exact square discriminant: the roots themselves tie rather than win.
+        let (time, distance) = (1 << 60, (1 << 118) - 1);
+        assert_eq!(ways(time, distance), BigUint::from(1_u32));
+        assert!(ways(time, distance + 1).is_zero());
+        assert_eq!(
+            ways(time, (1 << 118) - (1 << 60)),
+            BigUint::from((1_u32 << 31) - 1)
+        );
+
+        // Squaring this time overflows u128, which no longer matters.
+        assert_eq!(ways(u128::MAX / 2, 1), BigUint::from(u128::MAX / 2 - 1));
+    }
+
+    #[test]
+    fn test_hundreds_of_digits() {
+        // time = 2m and distance = m^2 - k^2, so holds m - k < h < m + k win.
+        let m = big(&format!("1{}", "0".repeat(150)));
+        let k = big(&format!("1{}", "0".repeat(40)));
+        let time = m.clone() << 1;
+        let distance = &(&m * &m) - &(&k * &k);
+        assert_eq!(distance.to_string().len(), 300);
+
+        let expected = &(k.clone() << 1) - &BigUint::from(1_u32);
+        assert_eq!(ways_to_win(&time, &distance), expected);
+        assert_eq!(
+            ways_to_win(&time, &(&distance - &BigUint::from(1_u32))),
+            &expected + &BigUint::from(2_u32)
+        );
+
+        // Only the midpoint beats m^2 - 1, and nothing beats m^2.
+        let best = &m * &m;
+        let one = BigUint::from(1_u32);
+        assert_eq!(ways_to_win(&time, &(&best - &one)), one);
+        assert!(ways_to_win(&time, &best).is_zero());
+
+        // Odd times have two symmetric midpoints.
+        let odd = &time + &one;
+        let best = &m * &(&m + &one);
+        assert_eq!(ways_to_win(&odd, &(&best - &one)), BigUint::from(2_u32));
+        assert!(ways_to_win(&odd, &best).is_zero());
+    }
+
+    #[test]
+    fn test_parse_races() {
+        let races = Races::parse("Time:      7  15   30\nDistance:  9  40  200\n").unwrap();
+        assert_eq!(races.times.len(), 3);
+        assert_eq!(races.distances[2], BigUint::from(200_u32));
+        assert_eq!(races.kerned_time, BigUint::from(71530_u32));
+        assert_eq!(races.kerned_distance, BigUint::from(940200_u32));
+
+        // Spacing around the headers doesn't matter.
+        let races = Races::parse("\nTime:7\t15 30\n\n  Distance :9 40  200\n\n").unwrap();
+        assert_eq!(races.kerned_time, BigUint::from(71530_u32));
+        assert_eq!(races.kerned_distance, BigUint::from(940200_u32));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |input: &str| Races::parse(input).err().unwrap().to_string();
+
+        assert_eq!(err(""), "Missing \"Time:\" line");
+        assert_eq!(err("Time: 7"), "Missing \"Distance:\" line");
+        assert_eq!(
+            err("Distance: 9\nTime: 7"),
+            "Line 1: expected \"Time:\" header, found \"Distance:\""
+        );
+        assert_eq!(
+            err("Time 7\nDistance: 9"),
+            "Line 1: expected \"Time:\" header"
+        );
+        assert_eq!(err("Time:\nDistance: 9"), "Line 1: no time values");
+        assert_eq!(
+            err("Time: 7 15\nDistance: 9 4x0"),
+            "Line 2, column 13: invalid distance \"4x0\""
+        );
+        assert_eq!(
+            err("Time: 7 -15\nDistance: 9 40"),
+            "Line 1, column 9: invalid time \"-15\""
+        );
+        assert_eq!(
+            err("Time: 7 15 30\nDistance: 9 40"),
+            "Line 1 has 3 times but line 2 has 2 distances"
+        );
+        assert_eq!(
+            err("Time: 7\nDistance: 9\nTime: 8"),
+            "Line 3: unexpected input after \"Distance:\" line"
+        );
+    }
+
+    #[cfg(test)]
+    fn physics(
+        acceleration: u32,
+        initial_speed: u32,
+        speed_cap: Option<u32>,
+        drag: u32,
+    ) -> Physics {
+        Physics {
+            acceleration,
+            initial_speed,
+            speed_cap,
+            drag,
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        let d = |p: &Physics, time: u32, hold: u32| p.distance(&time.into(), &hold.into());
+
+        assert_eq!(d(&Physics::default(), 7, 3), BigUint::from(12_u32));
+        assert_eq!(d(&physics(2, 1, None, 0), 7, 3), BigUint::from(28_u32));
+        assert_eq!(d(&physics(2, 1, Some(5), 0), 7, 3), BigUint::from(20_u32));
+        // Speed 7 with drag 3 covers 7 + 4 + 1 before stopping.
+        assert_eq!(d(&physics(1, 0, None, 3), 20, 7), BigUint::from(12_u32));
+        assert_eq!(d(&physics(1, 0, None, 3), 9, 7), BigUint::from(11_u32));
+        assert!(d(&physics(1, 0, None, 3), 7, 7).is_zero());
+    }
+
+    #[test]
+    fn test_models_match_brute_force() {
+        let caps = [None, Some(0), Some(3)];
+        for acceleration in 0..3 {
+            for initial_speed in 0..3 {
+                for speed_cap in caps {
+                    for drag in 0..3 {
+                        let p = physics(acceleration, initial_speed, speed_cap, drag);
+                        for time in 0..10_u32 {
+                            let time = BigUint::from(time);
+                            let reach: Vec<BigUint> = (0..=time.to_string().parse().unwrap())
+                                .map(|h: u32| p.distance(&time, &h.into()))
+                                .collect();
+                            for target in &reach {
+                                for distance in [target.clone(), target + &BigUint::from(1_u32)] {
+                                    let expected = reach.iter().filter(|r| **r > distance).count();
+                                    assert_eq!(
+                                        p.ways_to_win(&time, &distance),
+                                        BigUint::from(expected as u32),
+                                        "{:?} time={} distance={}",
+                                        p,
+                                        time,
+                                        distance
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_models() {
+        let time = big(&format!("1{}", "0".repeat(120)));
+        let one = BigUint::from(1_u32);
+
+        // Once capped at c the boat covers c * (time - h), which beats c * (time - 2c) for
+        // exactly the c holds c..2c; holding for less is far too slow.
+        let cap = 1_000_000;
+        let c = BigUint::from(cap);
+        let distance = &c * &(&time - &(c.clone() << 1));
+        assert_eq!(physics(1, 0, Some(cap), 0).ways_to_win(&time, &distance), c);
+
+        // A capped model whose cap is never reached agrees with the closed form.
+        let (t, d) = (BigUint::from(71530_u32), BigUint::from(940200_u32));
+        assert_eq!(
+            physics(1, 0, Some(u32::MAX), 0).ways_to_win(&t, &d),
+            BigUint::from(71503_u32)
+        );
+
+        // Every hold but the last moves when there is no record to beat.
+        assert_eq!(
+            physics(3, 5, None, 0).ways_to_win(&time, &BigUint::zero()),
+            time
+        );
+        assert_eq!(
+            physics(1, 0, None, 1).ways_to_win(&time, &BigUint::zero()),
+            &time - &one
+        );
+    }
 }
@@ -4,23 +4,14 @@ use std::error::Error;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 use std::result::Result;
+use std::time::Instant;
 
-const CARD_STRENGTH: [char; 13] = [
-    '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A',
-];
+const CARD_STRENGTH: &str = "23456789TJQKA";
 
-const P2_CARD_STRENGTH: [char; 13] = [
-    'J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A',
-];
+const P2_CARD_STRENGTH: &str = "J23456789TQKA";
 
-#[derive(PartialEq, Eq)]
-struct Hand {
-    cards: Vec<char>,
-    bet: i32,
-    p2: bool,
-}
-
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// The puzzle's hand types. Their shapes make up the ranking of both predefined rule sets.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 enum HandType {
     HighCard,
     Pair,
@@ -31,8 +22,217 @@ enum HandType {
     FiveOfAKind,
 }
 
+impl HandType {
+    const ALL: [HandType; 7] = [
+        HandType::HighCard,
+        HandType::Pair,
+        HandType::TwoPair,
+        HandType::ThreeOfAKind,
+        HandType::FullHouse,
+        HandType::FourOfAKind,
+        HandType::FiveOfAKind,
+    ];
+
+    /// The groups of matching cards the type needs, largest first.
+    fn shape(self) -> Vec<u8> {
+        use HandType::*;
+
+        match self {
+            HighCard => vec![1],
+            Pair => vec![2],
+            TwoPair => vec![2, 2],
+            ThreeOfAKind => vec![3],
+            FullHouse => vec![3, 2],
+            FourOfAKind => vec![4],
+            FiveOfAKind => vec![5],
+        }
+    }
+}
+
+/// The rules a game of Camel Cards is played by.
+#[derive(Debug)]
+struct Rules {
+    /// Every card, weakest first.
+    order: Vec<char>,
+    /// Whether the card at each position of `order` is wild. Wild cards stand in for
+    /// whichever cards give the strongest hand type, but rank by `order` to break ties.
+    wild: Vec<bool>,
+    hand_size: usize,
+    /// Hand types weakest first, each as the groups of matching cards it needs, largest
+    /// first. A hand has the strongest type whose groups it can cover.
+    ranking: Vec<Vec<u8>>,
+    /// Bits per card strength in a packed sort key.
+    card_bits: u32,
+}
+
+impl Rules {
+    fn new(
+        order: &str,
+        wildcards: &str,
+        hand_size: usize,
+        mut ranking: Vec<Vec<u8>>,
+    ) -> Result<Rules, Box<dyn Error>> {
+        let order: Vec<char> = order.chars().collect();
+        if order.is_empty() {
+            return Err("Rules need at least one card".into());
+        }
+        if let Some(c) = order
+            .iter()
+            .find(|c| order.iter().filter(|x| x == c).count() > 1)
+        {
+            return Err(format!("Card {:?} appears twice in the card order", c).into());
+        }
+        let mut wild = vec![false; order.len()];
+        for c in wildcards.chars() {
+            let i = order
+                .iter()
+                .position(|x| *x == c)
+                .ok_or(format!("Wildcard {:?} is not in the card order", c))?;
+            wild[i] = true;
+        }
+
+        if hand_size == 0 {
+            return Err("Hands need at least one card".into());
+        }
+        let bits = |n: usize| usize::BITS - n.saturating_sub(1).leading_zeros();
+        let card_bits = bits(order.len()).max(1);
+        if (card_bits as usize)
+            .saturating_mul(hand_size)
+            .saturating_add(bits(ranking.len()) as usize)
+            > u64::BITS as usize
+        {
+            return Err(format!(
+                "{}-card hands are too large to sort by a u64 key",
+                hand_size
+            )
+            .into());
+        }
+
+        for shape in ranking.iter_mut() {
+            shape.sort_by(|a, b| b.cmp(a));
+            if shape.contains(&0) || shape.iter().map(|n| *n as usize).sum::<usize>() > hand_size {
+                return Err(
+                    format!("Impossible hand type for {} cards: {:?}", hand_size, shape).into(),
+                );
+            }
+        }
+        if !matches!(ranking.first(), Some(s) if s.iter().map(|n| *n as usize).sum::<usize>() <= 1)
+        {
+            return Err("The weakest hand type must need at most one card".into());
+        }
+
+        Ok(Rules {
+            order,
+            wild,
+            hand_size,
+            ranking,
+            card_bits,
+        })
+    }
+
+    fn standard_ranking() -> Vec<Vec<u8>> {
+        HandType::ALL.iter().map(|t| t.shape()).collect()
+    }
+
+    fn part1() -> Rules {
+        Rules::new(CARD_STRENGTH, "", 5, Rules::standard_ranking()).expect("valid part 1 rules")
+    }
+
+    /// Part 1 with `J` as the weakest card, and wild.
+    fn part2() -> Rules {
+        Rules::new(P2_CARD_STRENGTH, "J", 5, Rules::standard_ranking()).expect("valid part 2 rules")
+    }
+
+    fn wildcards(&self) -> String {
+        self.order
+            .iter()
+            .zip(&self.wild)
+            .filter(|(_, w)| **w)
+            .map(|(c, _)| *c)
+            .collect()
+    }
+
+    /// The card's rank in the strength order, weakest first.
+    fn strength(&self, card: char) -> Option<u32> {
+        self.order.iter().position(|&x| x == card).map(|i| i as u32)
+    }
+
+    fn hand_type(&self, strengths: &[u32]) -> usize {
+        let mut counts = vec![0_u8; self.order.len()];
+        let mut wild_count = 0;
+        for s in strengths {
+            if self.wild[*s as usize] {
+                wild_count += 1;
+            } else {
+                counts[*s as usize] += 1;
+            }
+        }
+
+        let groups = counts.into_iter().filter(|n| *n > 0).collect();
+        self.best_rank(groups, wild_count)
+    }
+
+    /// The strongest hand type reachable by letting each wildcard join any group of
+    /// matching cards or start a new one. Under the standard ranking that is always the
+    /// largest group, but other rankings can prefer, say, two pair to three of a kind.
+    fn best_rank(&self, mut groups: Vec<u8>, wild_count: u8) -> usize {
+        // Wildcards are placed in non-decreasing group order so each placement is tried
+        // once.
+        fn place(rules: &Rules, groups: &mut Vec<u8>, wild_count: u8, from: usize) -> usize {
+            if wild_count == 0 {
+                let mut sorted = groups.clone();
+                sorted.sort_unstable_by(|a, b| b.cmp(a));
+                return rules.rank(&sorted);
+            }
+
+            let mut best = 0;
+            for i in from..groups.len() {
+                groups[i] += 1;
+                best = best.max(place(rules, groups, wild_count - 1, i));
+                groups[i] -= 1;
+            }
+            groups.push(1);
+            best = best.max(place(rules, groups, wild_count - 1, groups.len() - 1));
+            groups.pop();
+            best
+        }
+
+        place(self, &mut groups, wild_count, 0)
+    }
+
+    /// The strongest hand type covered by groups of matching cards, largest first.
+    fn rank(&self, groups: &[u8]) -> usize {
+        self.ranking
+            .iter()
+            .rposition(|shape| {
+                shape
+                    .iter()
+                    .enumerate()
+                    .all(|(i, need)| groups.get(i).is_some_and(|g| g >= need))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Hand type in the high bits, then each card's strength in order, so comparing keys
+    /// compares hands.
+    fn key(&self, hand_type: usize, strengths: &[u32]) -> u64 {
+        strengths
+            .iter()
+            .fold(hand_type as u64, |key, s| key << self.card_bits | *s as u64)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Hand {
+    cards: Vec<char>,
+    bet: i32,
+    /// Index into the ranking of the rules the hand was parsed with.
+    hand_type: usize,
+    key: u64,
+}
+
 impl Hand {
-    fn parse(line: &String, p2: bool) -> Result<Hand, Box<dyn Error>> {
+    fn parse(line: &String, rules: &Rules) -> Result<Hand, Box<dyn Error>> {
         let mut parts = line.split_whitespace();
 
         let cards: Vec<char> = parts
@@ -41,8 +241,12 @@ impl Hand {
             .map(|s| s.chars())?
             .collect();
 
-        if cards.len() != 5 {
-            return Err(format!("Too many cards for a hand, line={}", line).into());
+        if cards.len() != rules.hand_size {
+            return Err(format!(
+                "Expected {} cards in a hand, line={}",
+                rules.hand_size, line
+            )
+            .into());
         }
 
         let bet = parts
@@ -50,103 +254,237 @@ impl Hand {
             .ok_or(format!("missing bet in hand, line={}", line))
             .map(str::parse::<i32>)??;
 
-        Ok(Hand { cards, bet, p2 })
+        let strengths = cards
+            .iter()
+            .map(|c| {
+                rules
+                    .strength(*c)
+                    .ok_or(format!("Unknown card {:?} in hand, line={}", c, line))
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        let hand_type = rules.hand_type(&strengths);
+        let key = rules.key(hand_type, &strengths);
+
+        Ok(Hand {
+            cards,
+            bet,
+            hand_type,
+            key,
+        })
     }
+}
 
-    fn hand_type(&self) -> HandType {
-        use HandType::*;
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-        let mut counter: HashMap<char, i8> = HashMap::new();
-        for c in &self.cards {
+impl Ord for Hand {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// The original comparison, which classifies both hands and looks up every card's
+/// strength on each call. Kept as the baseline for `bench`.
+fn naive_cmp(rules: &Rules, a: &Hand, b: &Hand) -> Ordering {
+    let naive_type = |hand: &Hand| {
+        let mut counter: HashMap<char, u8> = HashMap::new();
+        for c in &hand.cards {
             *counter.entry(*c).or_insert(0) += 1;
         }
+        let mut wild_count = 0;
+        for (c, wild) in rules.order.iter().zip(&rules.wild) {
+            if *wild {
+                wild_count += counter.remove(c).unwrap_or(0);
+            }
+        }
 
-        let joker_count = if self.p2 {
-            counter.remove(&'J').unwrap_or(0)
-        } else {
-            0
-        };
-
-        if joker_count == 5 {
-            return FiveOfAKind;
+        rules.best_rank(counter.into_values().collect(), wild_count)
+    };
+
+    match naive_type(a).cmp(&naive_type(b)) {
+        Less => Less,
+        Greater => Greater,
+        Equal => {
+            for (a_card, b_card) in a.cards.iter().zip(b.cards.iter()) {
+                let a_strength = rules.order.iter().position(|x| x == a_card).unwrap();
+                let b_strength = rules.order.iter().position(|x| x == b_card).unwrap();
+
+                match a_strength.cmp(&b_strength) {
+                    Less => return Less,
+                    Greater => return Greater,
+                    _ => (),
+                }
+            }
+            Equal
         }
+    }
+}
 
-        let mut counts = counter.values_mut().collect::<Vec<_>>();
-        counts.sort_by(|a, b| b.cmp(a));
-        *counts[0] += joker_count;
+/// Deterministic pseudo-random hands for benchmarking, from a xorshift generator.
+fn generate_hands(count: usize, rules: &Rules) -> Vec<String> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    (0..count)
+        .map(|_| {
+            let cards: String = (0..rules.hand_size)
+                .map(|_| rules.order[(next() % rules.order.len() as u64) as usize])
+                .collect();
+            format!("{} {}", cards, next() % 1000)
+        })
+        .collect()
+}
 
-        if *counts[0] == 5 {
-            return FiveOfAKind;
-        }
+/// Times sorting `count` generated hands with the naive comparison and with packed keys.
+fn bench(count: usize, rule_sets: &[(&str, Rules)]) -> Result<(), Box<dyn Error>> {
+    for (name, rules) in rule_sets {
+        let lines = generate_hands(count, rules);
 
-        match (*counts[0], *counts[1]) {
-            (4, _) => FourOfAKind,
-            (3, 2) => FullHouse,
-            (3, _) => ThreeOfAKind,
-            (2, 2) => TwoPair,
-            (2, _) => Pair,
-            _ => HighCard,
-        }
+        let parse = || {
+            lines
+                .iter()
+                .map(|l| Hand::parse(l, rules))
+                .collect::<Result<Vec<Hand>, _>>()
+        };
+
+        let start = Instant::now();
+        let mut hands = parse()?;
+        let parsed = start.elapsed();
+        let start = Instant::now();
+        hands.sort_unstable_by_key(|h| h.key);
+        let keyed = start.elapsed();
+
+        let mut hands = parse()?;
+        let start = Instant::now();
+        hands.sort_by(|a, b| naive_cmp(rules, a, b));
+        let naive = start.elapsed();
+
+        println!(
+            "{}: {} hands, parse {:?}, naive sort {:?}, key sort {:?}",
+            name, count, parsed, naive, keyed
+        );
     }
+
+    Ok(())
 }
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
-    }
+struct Options {
+    /// Number of hands to generate for `bench`, instead of solving the input.
+    bench: Option<usize>,
+    /// Rules given on the command line, replacing the part 1 and part 2 rules.
+    rules: Option<Rules>,
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let strength = if self.p2 {
-            P2_CARD_STRENGTH
-        } else {
-            CARD_STRENGTH
-        };
-        match self.hand_type().cmp(&other.hand_type()) {
-            Less => Less,
-            Greater => Greater,
-            Equal => {
-                for (self_card, other_card) in self.cards.iter().zip(other.cards.iter()) {
-                    let self_strength = strength.iter().position(|&x| x == *self_card).unwrap();
-                    let other_strength = strength.iter().position(|&x| x == *other_card).unwrap();
-
-                    match self_strength.cmp(&other_strength) {
-                        Less => return Less,
-                        Greater => return Greater,
-                        _ => (),
+impl Options {
+    /// Rule options start from `--rules part1` (the default) or `--rules part2` and change
+    /// only what they name.
+    fn parse(args: &[String]) -> Result<Options, Box<dyn Error>> {
+        let mut bench = None;
+        let mut custom = false;
+        let mut base = Rules::part1();
+        let mut order = None;
+        let mut wildcards = None;
+        let mut hand_size = None;
+        let mut ranking = None;
+
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "bench" {
+                bench = Some(match args.next_if(|a| !a.starts_with("--")) {
+                    Some(n) => n.parse()?,
+                    None => 1_000_000,
+                });
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or(format!("{} requires a value", arg))?
+                .as_str();
+            custom = true;
+            match arg.as_str() {
+                "--rules" => {
+                    base = match value {
+                        "part1" => Rules::part1(),
+                        "part2" => Rules::part2(),
+                        x => return Err(format!("Unknown rule set: {}", x).into()),
                     }
                 }
-                Equal
+                "--cards" => order = Some(value.to_string()),
+                "--wild" => wildcards = Some(value.to_string()),
+                "--hand-size" => hand_size = Some(value.parse()?),
+                "--ranking" => ranking = Some(parse_ranking(value)?),
+                x => return Err(format!("Unknown camel cards option: {}", x).into()),
             }
         }
+
+        let rules = match custom {
+            false => None,
+            true => Some(Rules::new(
+                &order.unwrap_or_else(|| base.order.iter().collect()),
+                &wildcards.unwrap_or_else(|| base.wildcards()),
+                hand_size.unwrap_or(base.hand_size),
+                ranking.unwrap_or(base.ranking),
+            )?),
+        };
+
+        Ok(Options { bench, rules })
     }
 }
 
-pub fn camel_cards(f: File) -> Result<(), Box<dyn Error>> {
-    let lines: Vec<String> = BufReader::new(f).lines().collect::<Result<_, _>>()?;
+/// Parses hand types weakest first as comma-separated group sizes, e.g. `1,2,22,3,32,4,5`
+/// for the standard ranking.
+fn parse_ranking(s: &str) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    s.split(',')
+        .map(|shape| {
+            shape
+                .trim()
+                .chars()
+                .map(|c| {
+                    c.to_digit(10)
+                        .map(|d| d as u8)
+                        .ok_or(format!("Invalid hand type {:?} in ranking", shape).into())
+                })
+                .collect()
+        })
+        .collect()
+}
 
-    let p1_hands = lines
-        .iter()
-        .map(|l| Hand::parse(l, false))
-        .collect::<Result<Vec<Hand>, _>>()?;
+pub fn camel_cards(f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = Options::parse(args)?;
 
-    let p2_hands = lines
-        .iter()
-        .map(|l| Hand::parse(l, true))
-        .collect::<Result<Vec<Hand>, _>>()?;
+    let rule_sets = match options.rules {
+        Some(rules) => vec![("Custom rules", rules)],
+        None => vec![("Part 1", Rules::part1()), ("Part 2", Rules::part2())],
+    };
 
-    let p1 = winnings(p1_hands);
-    println!("Part 1: {}", p1);
+    if let Some(count) = options.bench {
+        return bench(count, &rule_sets);
+    }
 
-    let p2 = winnings(p2_hands);
-    println!("Part 2: {}", p2);
+    let lines: Vec<String> = BufReader::new(f).lines().collect::<Result<_, _>>()?;
+    for (name, rules) in &rule_sets {
+        let hands = lines
+            .iter()
+            .map(|l| Hand::parse(l, rules))
+            .collect::<Result<Vec<Hand>, _>>()?;
+        println!("{}: {}", name, winnings(hands));
+    }
 
     Ok(())
 }
 
 fn winnings(mut hands: Vec<Hand>) -> i32 {
-    hands.sort();
+    hands.sort_unstable_by_key(|h| h.key);
 
     hands
         .iter()
@@ -164,19 +502,25 @@ mod tests {
     fn test_hand_type() {
         let cases = [
             (
-                Hand::parse(&"T55J5 0".to_string(), false).unwrap(),
+                Hand::parse(&"T55J5 0".to_string(), &Rules::part1()).unwrap(),
                 ThreeOfAKind,
             ),
             (
-                Hand::parse(&"QQQJA 0".to_string(), false).unwrap(),
+                Hand::parse(&"QQQJA 0".to_string(), &Rules::part1()).unwrap(),
                 ThreeOfAKind,
             ),
-            (Hand::parse(&"32T3K 0".to_string(), false).unwrap(), Pair),
-            (Hand::parse(&"KK677 0".to_string(), false).unwrap(), TwoPair),
+            (
+                Hand::parse(&"32T3K 0".to_string(), &Rules::part1()).unwrap(),
+                Pair,
+            ),
+            (
+                Hand::parse(&"KK677 0".to_string(), &Rules::part1()).unwrap(),
+                TwoPair,
+            ),
         ];
 
         for c in cases {
-            assert_eq!(c.0.hand_type(), c.1)
+            assert_eq!(c.0.hand_type, c.1 as usize)
         }
     }
 
@@ -184,23 +528,29 @@ mod tests {
     fn test_p2_hand_type() {
         let cases = [
             (
-                Hand::parse(&"T55J5 0".to_string(), true).unwrap(),
+                Hand::parse(&"T55J5 0".to_string(), &Rules::part2()).unwrap(),
                 FourOfAKind,
             ),
             (
-                Hand::parse(&"QQQJA 0".to_string(), true).unwrap(),
+                Hand::parse(&"QQQJA 0".to_string(), &Rules::part2()).unwrap(),
                 FourOfAKind,
             ),
             (
-                Hand::parse(&"KTJJT 0".to_string(), true).unwrap(),
+                Hand::parse(&"KTJJT 0".to_string(), &Rules::part2()).unwrap(),
                 FourOfAKind,
             ),
-            (Hand::parse(&"32T3K 0".to_string(), true).unwrap(), Pair),
-            (Hand::parse(&"KK677 0".to_string(), true).unwrap(), TwoPair),
+            (
+                Hand::parse(&"32T3K 0".to_string(), &Rules::part2()).unwrap(),
+                Pair,
+            ),
+            (
+                Hand::parse(&"KK677 0".to_string(), &Rules::part2()).unwrap(),
+                TwoPair,
+            ),
         ];
 
         for c in cases {
-            assert_eq!(c.0.hand_type(), c.1)
+            assert_eq!(c.0.hand_type, c.1 as usize)
         }
     }
 
@@ -208,13 +558,13 @@ mod tests {
     fn test_hand_cmp() {
         let cases = [
             (
-                Hand::parse(&"T55J5 0".to_string(), false).unwrap(),
-                Hand::parse(&"QQQJA 0".to_string(), false).unwrap(),
+                Hand::parse(&"T55J5 0".to_string(), &Rules::part1()).unwrap(),
+                Hand::parse(&"QQQJA 0".to_string(), &Rules::part1()).unwrap(),
                 Less,
             ),
             (
-                Hand::parse(&"KK677 0".to_string(), false).unwrap(),
-                Hand::parse(&"KTJJT 0".to_string(), false).unwrap(),
+                Hand::parse(&"KK677 0".to_string(), &Rules::part1()).unwrap(),
+                Hand::parse(&"KTJJT 0".to_string(), &Rules::part1()).unwrap(),
                 Greater,
             ),
         ];
@@ -227,11 +577,11 @@ mod tests {
     #[test]
     fn test_winnings() {
         let hands = vec![
-            Hand::parse(&"32T3K 765".to_string(), false).unwrap(),
-            Hand::parse(&"T55J5 684".to_string(), false).unwrap(),
-            Hand::parse(&"KK677 28".to_string(), false).unwrap(),
-            Hand::parse(&"KTJJT 220".to_string(), false).unwrap(),
-            Hand::parse(&"QQQJA 483".to_string(), false).unwrap(),
+            Hand::parse(&"32T3K 765".to_string(), &Rules::part1()).unwrap(),
+            Hand::parse(&"T55J5 684".to_string(), &Rules::part1()).unwrap(),
+            Hand::parse(&"KK677 28".to_string(), &Rules::part1()).unwrap(),
+            Hand::parse(&"KTJJT 220".to_string(), &Rules::part1()).unwrap(),
+            Hand::parse(&"QQQJA 483".to_string(), &Rules::part1()).unwrap(),
         ];
 
         let actual = winnings(hands);
@@ -241,14 +591,188 @@ mod tests {
     #[test]
     fn test_p2_winnings() {
         let hands = vec![
-            Hand::parse(&"32T3K 765".to_string(), true).unwrap(),
-            Hand::parse(&"T55J5 684".to_string(), true).unwrap(),
-            Hand::parse(&"KK677 28".to_string(), true).unwrap(),
-            Hand::parse(&"KTJJT 220".to_string(), true).unwrap(),
-            Hand::parse(&"QQQJA 483".to_string(), true).unwrap(),
+            Hand::parse(&"32T3K 765".to_string(), &Rules::part2()).unwrap(),
+            Hand::parse(&"T55J5 684".to_string(), &Rules::part2()).unwrap(),
+            Hand::parse(&"KK677 28".to_string(), &Rules::part2()).unwrap(),
+            Hand::parse(&"KTJJT 220".to_string(), &Rules::part2()).unwrap(),
+            Hand::parse(&"QQQJA 483".to_string(), &Rules::part2()).unwrap(),
         ];
 
         let actual = winnings(hands);
         assert_eq!(actual, 5905);
     }
+
+    #[test]
+    fn test_unknown_cards() {
+        assert!(Hand::parse(&"32T3X 1".to_string(), &Rules::part1()).is_err());
+        assert!(Hand::parse(&"32t3K 1".to_string(), &Rules::part2()).is_err());
+    }
+
+    #[test]
+    fn test_key_matches_naive_order() {
+        let rule_sets = [
+            Rules::part1(),
+            Rules::part2(),
+            Rules::new("J23456789TQKA", "2J", 5, Rules::standard_ranking()).unwrap(),
+            Rules::new("23456", "6", 6, six_card_ranking()).unwrap(),
+        ];
+        for rules in &rule_sets {
+            let hands = generate_hands(100, rules)
+                .iter()
+                .map(|l| Hand::parse(l, rules).unwrap())
+                .collect::<Vec<_>>();
+            for a in &hands {
+                for b in hands.iter().step_by(6) {
+                    assert_eq!(
+                        a.cmp(b),
+                        naive_cmp(rules, a, b),
+                        "{:?} vs {:?}",
+                        a.cards,
+                        b.cards
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn six_card_ranking() -> Vec<Vec<u8>> {
+        parse_ranking("1,2,22,3,222,32,33,4,42,5,6").unwrap()
+    }
+
+    #[test]
+    fn test_two_wildcards() {
+        let rules = Rules::new("J23456789TQKA", "2J", 5, Rules::standard_ranking()).unwrap();
+        let hand_type = |cards: &str| {
+            Hand::parse(&format!("{} 0", cards), &rules)
+                .unwrap()
+                .hand_type
+        };
+
+        assert_eq!(hand_type("J2345"), ThreeOfAKind as usize);
+        assert_eq!(hand_type("22JJK"), FiveOfAKind as usize);
+        assert_eq!(hand_type("2KKQQ"), FullHouse as usize);
+        assert_eq!(hand_type("345TQ"), HighCard as usize);
+
+        // Wildcards still break ties by their own strength.
+        let a = Hand::parse(&"JKKK3 0".to_string(), &rules).unwrap();
+        let b = Hand::parse(&"2KKK3 0".to_string(), &rules).unwrap();
+        assert_eq!(a.cmp(&b), Less);
+    }
+
+    #[test]
+    fn test_six_card_hands() {
+        let rules = Rules::new("23456789TJQKA", "J", 6, six_card_ranking()).unwrap();
+        let hand_type = |cards: &str| {
+            let hand = Hand::parse(&format!("{} 0", cards), &rules).unwrap();
+            rules.ranking[hand.hand_type].clone()
+        };
+
+        assert_eq!(hand_type("KKKKKK"), vec![6]);
+        assert_eq!(hand_type("KKKJQQ"), vec![4, 2]);
+        assert_eq!(hand_type("KKK2QQ"), vec![3, 2]);
+        assert_eq!(hand_type("KKQQ22"), vec![2, 2, 2]);
+        assert_eq!(hand_type("KKKQQQ"), vec![3, 3]);
+        assert_eq!(hand_type("23456J"), vec![2]);
+
+        assert!(Hand::parse(&"KKKKK 0".to_string(), &rules).is_err());
+        let lines = ["KKKQQQ 2", "KKKKQQ 3", "23456J 1"].map(String::from);
+        let hands = lines
+            .iter()
+            .map(|l| Hand::parse(l, &rules).unwrap())
+            .collect();
+        assert_eq!(winnings(hands), 1 + 2 * 2 + 3 * 3);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let ranking = Rules::standard_ranking;
+        assert!(Rules::new("", "", 5, ranking()).is_err());
+        assert!(Rules::new("AKQA", "", 5, ranking()).is_err());
+        assert!(Rules::new("AKQ", "J", 5, ranking()).is_err());
+        assert!(Rules::new("AKQ", "", 0, ranking()).is_err());
+        assert!(Rules::new("AKQ", "", 4, ranking()).is_err());
+        assert!(Rules::new("AKQ", "", 5, vec![vec![2], vec![3]]).is_err());
+        assert!(Rules::new("23456789TJQKA", "", 17, vec![vec![1]]).is_err());
+        assert!(Rules::new("23456789TJQKA", "", 16, vec![vec![1]]).is_ok());
+        assert!(parse_ranking("1,2,x").is_err());
+
+        // Oversized input is rejected rather than overflowing.
+        let huge = parse_ranking(&"9".repeat(29)).unwrap();
+        assert!(Rules::new("23456789TJQKA", "", 1000, huge.clone()).is_err());
+        assert!(Rules::new("A", "", 64, huge).is_err());
+        assert!(Rules::new("AK", "", usize::MAX, ranking()).is_err());
+    }
+
+    #[cfg(test)]
+    fn brute_force_type(rules: &Rules, cards: &[char]) -> usize {
+        // Each wildcard may copy a plain card in the hand, or become a card matching nothing
+        // else; a plain card absent from the hand behaves like the latter.
+        let wild_at: Vec<usize> = (0..cards.len())
+            .filter(|i| rules.wild[rules.strength(cards[*i]).unwrap() as usize])
+            .collect();
+        let mut choices: Vec<char> = (0..cards.len())
+            .filter(|i| !wild_at.contains(i))
+            .map(|i| cards[i])
+            .collect();
+        choices.sort();
+        choices.dedup();
+        choices.extend(['a', 'b', 'c', 'd', 'e', 'f'].iter().take(wild_at.len()));
+
+        let mut best = 0;
+        for n in 0..choices.len().pow(wild_at.len() as u32) {
+            let mut hand = cards.to_vec();
+            let mut n = n;
+            for i in &wild_at {
+                hand[*i] = choices[n % choices.len()];
+                n /= choices.len();
+            }
+            let mut counter: HashMap<char, u8> = HashMap::new();
+            for c in &hand {
+                *counter.entry(*c).or_insert(0) += 1;
+            }
+            let mut groups: Vec<u8> = counter.into_values().collect();
+            groups.sort_by(|a, b| b.cmp(a));
+            best = best.max(rules.rank(&groups));
+        }
+        best
+    }
+
+    #[test]
+    fn test_nonstandard_ranking() {
+        let ranking = parse_ranking("1,2,3,22,32,4,5").unwrap();
+        let rules = Rules::new(P2_CARD_STRENGTH, "J", 5, ranking).unwrap();
+
+        // The joker pairs the Q for two pair, which outranks three of a kind here.
+        let hand = Hand::parse(&"KKQ2J 1".to_string(), &rules).unwrap();
+        assert_eq!(rules.ranking[hand.hand_type], vec![2, 2]);
+        let hands = ["KKQ2J 1", "AAA23 2"]
+            .iter()
+            .map(|l| Hand::parse(&l.to_string(), &rules).unwrap())
+            .collect();
+        assert_eq!(winnings(hands), 4);
+
+        let rule_sets = [
+            rules,
+            Rules::new("AKQ2J", "2J", 5, parse_ranking("1,2,3,22,32,4,5").unwrap()).unwrap(),
+            Rules::new(
+                "AKQ2J",
+                "J",
+                6,
+                parse_ranking("1,2,22,222,3,4,33,5,42,6").unwrap(),
+            )
+            .unwrap(),
+        ];
+        for rules in &rule_sets {
+            for line in generate_hands(150, rules) {
+                let hand = Hand::parse(&line, rules).unwrap();
+                assert_eq!(
+                    hand.hand_type,
+                    brute_force_type(rules, &hand.cards),
+                    "{:?}",
+                    hand.cards
+                );
+            }
+        }
+    }
 }
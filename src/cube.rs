@@ -1,31 +1,122 @@
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 use std::result::Result;
+use std::sync::Mutex;
 
+#[derive(Debug)]
 struct Game {
-    id: u16,
+    id: u32,
     rounds: Vec<Round>,
 }
 
+/// Colors of the original puzzle, which sort, and print, first and in this order.
+const ORIGINAL_COLORS: [&str; 3] = ["red", "green", "blue"];
+
+/// Interned cube color. Original colors order by their place in `ORIGINAL_COLORS`, and
+/// any others after them by name, so the order never depends on what was parsed first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Color {
+    rank: usize,
+    name: &'static str,
+}
+
+lazy_static! {
+    // Each distinct name is leaked once and shared by every Color with that name.
+    static ref COLORS: Mutex<Vec<&'static str>> = Mutex::new(ORIGINAL_COLORS.to_vec());
+}
+
+impl Color {
+    fn intern(name: &str) -> Color {
+        let mut colors = COLORS.lock().unwrap();
+        let name = match colors.iter().find(|c| **c == name) {
+            Some(c) => *c,
+            None => {
+                let leaked: &'static str = Box::leak(name.to_string().into_boxed_str());
+                colors.push(leaked);
+                leaked
+            }
+        };
+        let rank = ORIGINAL_COLORS
+            .iter()
+            .position(|c| *c == name)
+            .unwrap_or(ORIGINAL_COLORS.len());
+        Color { rank, name }
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 struct Round {
-    red: u8,
-    green: u8,
-    blue: u8,
+    counts: BTreeMap<Color, u32>,
+}
+
+/// Bag used when neither the command line nor the input header provides one.
+const DEFAULT_BAG: &str = "12 red, 13 green, 14 blue";
+
+/// Prefix of an optional input line describing a bag, e.g. `Bag: 12 red, 13 green, 14 blue`.
+const BAG_HEADER: &str = "Bag:";
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Solve,
+    WhyImpossible,
+    Stats,
+}
+
+struct Options {
+    mode: Mode,
+    bags: Vec<Round>,
+    min_bag: bool,
+    games: Option<Vec<u32>>,
 }
 
-const ROUNDLIMIT: Round = Round {
-    red: 12,
-    green: 13,
-    blue: 14,
-};
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, Box<dyn Error>> {
+        let mut options = Options {
+            mode: Mode::Solve,
+            bags: Vec::new(),
+            min_bag: false,
+            games: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bag" => {
+                    let spec = args.next().ok_or("--bag requires a value")?;
+                    options.bags.push(Round::parse(spec)?);
+                }
+                "why-impossible" => options.mode = Mode::WhyImpossible,
+                "stats" => options.mode = Mode::Stats,
+                "--min-bag" => options.min_bag = true,
+                "--games" => {
+                    let ids = args
+                        .next()
+                        .ok_or("--games requires a comma-separated list of ids")?
+                        .split(',')
+                        .map(|id| id.trim().parse::<u32>())
+                        .collect::<Result<Vec<u32>, _>>()?;
+                    options.games = Some(ids);
+                }
+                x => return Err(format!("Unknown cube option: {}", x).into()),
+            }
+        }
+
+        Ok(options)
+    }
+}
 
 impl Round {
     fn new() -> Round {
         Round {
-            red: 0,
-            green: 0,
-            blue: 0,
+            counts: BTreeMap::new(),
         }
     }
 
@@ -39,27 +130,76 @@ impl Round {
                 return Err(format!("Invalid cube draw format: {}", c).into());
             }
 
-            let count = parts[0].parse::<u8>()?;
-            let color = parts[1].to_lowercase();
+            let count = parts[0]
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid cube count {}: {}", parts[0], e))?;
+            let color = Color::intern(&parts[1].to_lowercase());
 
-            match color.as_str() {
-                "red" => round.red += count,
-                "green" => round.green += count,
-                "blue" => round.blue += count,
-                _ => return Err(format!("Invalid color: {}", color).into()),
-            }
+            let total = round.counts.entry(color).or_insert(0);
+            *total = total
+                .checked_add(count)
+                .ok_or(format!("Too many {} cubes in one draw", color.name()))?;
         }
 
-        return Ok(round);
+        Ok(round)
+    }
+
+    /// Number of cubes of `color` in the round; colors never drawn count as zero.
+    fn count(&self, color: Color) -> u32 {
+        self.counts.get(&color).copied().unwrap_or(0)
+    }
+
+    fn possible(&self, bag: &Round) -> bool {
+        self.counts.iter().all(|(c, n)| *n <= bag.count(*c))
+    }
+
+    /// Colors drawn beyond what `bag` holds, with the number of cubes over.
+    fn excess(&self, bag: &Round) -> Vec<(Color, u32)> {
+        self.counts
+            .iter()
+            .filter(|(c, n)| **n > bag.count(**c))
+            .map(|(c, n)| (*c, n - bag.count(*c)))
+            .collect()
     }
 
-    fn possible(&self) -> bool {
-        if self.red > ROUNDLIMIT.red || self.green > ROUNDLIMIT.green || self.blue > ROUNDLIMIT.blue
-        {
-            return false;
+    /// Total cubes in the round, widened so that many large counts can't overflow.
+    fn total(&self) -> u64 {
+        self.counts.values().map(|n| *n as u64).sum()
+    }
+
+    /// Probability of drawing exactly this round from `bag` without replacement, i.e. the
+    /// multivariate hypergeometric probability of the observed color counts.
+    fn likelihood(&self, bag: &Round) -> f64 {
+        if !self.possible(bag) {
+            return 0.0;
         }
 
-        return true;
+        let ways = bag
+            .counts
+            .iter()
+            .map(|(c, n)| ln_choose(*n as u64, self.count(*c) as u64))
+            .sum::<f64>();
+
+        (ways - ln_choose(bag.total(), self.total())).exp()
+    }
+
+    fn max(mut self, other: &Round) -> Round {
+        for (c, n) in &other.counts {
+            let count = self.counts.entry(*c).or_insert(0);
+            *count = std::cmp::max(*count, *n);
+        }
+        self
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let draws = self
+            .counts
+            .iter()
+            .map(|(c, n)| format!("{} {}", n, c.name()))
+            .collect::<Vec<String>>();
+        write!(f, "{}", draws.join(", "))
     }
 }
 
@@ -74,60 +214,287 @@ impl Game {
             .split(' ')
             .nth(1)
             .ok_or(format!("Invalid game segment: {}", parts[0]))?
-            .parse()?;
+            .parse()
+            .map_err(|e| format!("Invalid game id in {}: {}", parts[0], e))?;
 
         let rounds = parts[1]
             .split("; ")
-            .map(|s| Round::parse(s))
+            .enumerate()
+            .map(|(i, r)| {
+                Round::parse(r).map_err(|e| format!("Game {}, round {}: {}", id, i + 1, e).into())
+            })
             .collect::<Result<Vec<Round>, Box<dyn Error>>>()?;
 
         Ok(Game { id, rounds })
     }
 
+    fn possible(&self, bag: &Round) -> bool {
+        self.rounds.iter().all(|r| r.possible(bag))
+    }
+
+    /// Every (round index, color, excess) that rules the game out under `bag`.
+    fn violations(&self, bag: &Round) -> Vec<(usize, Color, u32)> {
+        self.rounds
+            .iter()
+            .enumerate()
+            .flat_map(|(i, r)| r.excess(bag).into_iter().map(move |(c, n)| (i, c, n)))
+            .collect()
+    }
+
+    /// Probability of the game's rounds under `bag`, with the cubes returned to the bag
+    /// between rounds.
+    fn likelihood(&self, bag: &Round) -> f64 {
+        self.rounds.iter().map(|r| r.likelihood(bag)).product()
+    }
+
     fn min_round(&self) -> Round {
-        return self.rounds.iter().fold(Round::new(), |mut acc, r| {
-            acc.red = std::cmp::max(acc.red, r.red);
-            acc.green = std::cmp::max(acc.green, r.green);
-            acc.blue = std::cmp::max(acc.blue, r.blue);
-            acc
-        })
+        self.rounds.iter().fold(Round::new(), Round::max)
     }
 
-    fn power(&self) -> u32 {
+    /// Product of the minimum cube counts over `palette`, so a color the game never
+    /// draws makes the power zero.
+    fn power(&self, palette: &BTreeSet<Color>) -> Result<u64, Box<dyn Error>> {
         let mr = self.min_round();
-        return mr.red as u32 * mr.green as u32 * mr.blue as u32;
+        palette
+            .iter()
+            .try_fold(1_u64, |acc, c| acc.checked_mul(mr.count(*c) as u64))
+            .ok_or(format!("Game {}: power overflows u64", self.id).into())
     }
 }
 
-pub fn cube(f: File) -> Result<(), Box<dyn Error>> {
+/// Colors that count towards part 2: red, green and blue, so the original puzzle's
+/// answer is unchanged even when one is never drawn, plus any other color in the games.
+fn palette(games: &[Game]) -> BTreeSet<Color> {
+    ORIGINAL_COLORS
+        .into_iter()
+        .map(Color::intern)
+        .chain(
+            games
+                .iter()
+                .flat_map(|g| g.rounds.iter().flat_map(|r| r.counts.keys().copied())),
+        )
+        .collect()
+}
+
+/// Smallest bag under which every given game is possible.
+fn min_bag<'a>(games: impl IntoIterator<Item = &'a Game>) -> Round {
+    games
+        .into_iter()
+        .fold(Round::new(), |acc, g| acc.max(&g.min_round()))
+}
+
+/// Beyond this many factors `ln_choose` switches from an exact product to Stirling's
+/// series, so huge counts take constant time.
+const LN_CHOOSE_TERMS: u64 = 1000;
+
+/// Natural log of the binomial coefficient `n` choose `k`, for `k <= n`.
+fn ln_choose(n: u64, k: u64) -> f64 {
+    let k = std::cmp::min(k, n - k);
+    if k <= LN_CHOOSE_TERMS {
+        return (1..=k)
+            .map(|i| ((n - k + i) as f64).ln() - (i as f64).ln())
+            .sum();
+    }
+
+    ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+}
+
+/// Natural log of `n!` from Stirling's series, accurate to well under 1e-12 for the
+/// `n > LN_CHOOSE_TERMS` it is used for.
+fn ln_factorial(n: u64) -> f64 {
+    let n = n as f64;
+    n * n.ln() - n + 0.5 * (2.0 * std::f64::consts::PI * n).ln() + 1.0 / (12.0 * n)
+        - 1.0 / (360.0 * n.powi(3))
+        + 1.0 / (1260.0 * n.powi(5))
+}
+
+/// For each color, how many draws across all games had each size.
+fn draw_sizes(games: &[Game]) -> BTreeMap<Color, BTreeMap<u32, usize>> {
+    let mut sizes: BTreeMap<Color, BTreeMap<u32, usize>> = BTreeMap::new();
+    for round in games.iter().flat_map(|g| &g.rounds) {
+        for (c, n) in &round.counts {
+            *sizes.entry(*c).or_default().entry(*n).or_insert(0) += 1;
+        }
+    }
+    sizes
+}
+
+/// The game needing the most cubes of `color`, i.e. the first to become impossible as
+/// the bag shrinks. Ties go to the earliest game.
+fn most_restrictive(games: &[Game], color: Color) -> Option<&Game> {
+    games
+        .iter()
+        .rev()
+        .max_by_key(|g| g.min_round().count(color))
+}
+
+/// Draw size distribution and most restrictive game per color, followed by each game's
+/// likelihood under every bag.
+fn stats(games: &[Game], bags: &[Round]) -> String {
+    let mut report = String::new();
+
+    for (color, sizes) in draw_sizes(games) {
+        let histogram = sizes
+            .iter()
+            .map(|(size, n)| format!("{}x{}", size, n))
+            .collect::<Vec<String>>();
+        report += &format!("{} draw sizes: {}\n", color.name(), histogram.join(", "));
+
+        if let Some(game) = most_restrictive(games, color) {
+            report += &format!(
+                "{} most restrictive: Game {} ({} cubes)\n",
+                color.name(),
+                game.id,
+                game.min_round().count(color)
+            );
+        }
+    }
+
+    for bag in bags {
+        report += &format!("Likelihood under {}:\n", bag);
+        for game in games {
+            report += &format!("  Game {}: {:.6e}\n", game.id, game.likelihood(bag));
+        }
+    }
+
+    report
+}
+
+/// Lists, per bag, each excluded game with the draws that exceed the bag, along with
+/// the game's minimum round and part 2 power.
+fn why_impossible(
+    games: &[Game],
+    bags: &[Round],
+    palette: &BTreeSet<Color>,
+) -> Result<String, Box<dyn Error>> {
+    let mut report = String::new();
+
+    for bag in bags {
+        report += &format!("Bag: {}\n", bag);
+        for game in games {
+            let violations = game.violations(bag);
+            if violations.is_empty() {
+                continue;
+            }
+
+            report += &format!(
+                "  Game {}: min round {} (power {})\n",
+                game.id,
+                game.min_round(),
+                game.power(palette)?
+            );
+            for (round, color, excess) in violations {
+                report += &format!(
+                    "    round {}: {} {} over\n",
+                    round + 1,
+                    color.name(),
+                    excess
+                );
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn cube(f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
     let reader = BufReader::new(f);
+    let options = Options::parse(args)?;
 
-    let mut sum = 0;
-    let mut powersum: u32 = 0;
+    let mut header_bags = Vec::new();
+    let mut games = Vec::new();
 
     for line in reader.lines() {
-        let game = Game::parse(&line?)?;
-        if game.rounds.iter().filter(|r| !r.possible()).count() == 0 {
-            sum += game.id;
+        let line = line?;
+        match line.strip_prefix(BAG_HEADER) {
+            Some(spec) => header_bags.push(Round::parse(spec)?),
+            None => games.push(Game::parse(&line)?),
+        }
+    }
+
+    let bags = if !options.bags.is_empty() {
+        options.bags
+    } else if !header_bags.is_empty() {
+        header_bags
+    } else {
+        vec![Round::parse(DEFAULT_BAG)?]
+    };
+
+    let palette = palette(&games);
+
+    match options.mode {
+        Mode::WhyImpossible => {
+            print!("{}", why_impossible(&games, &bags, &palette)?);
+            return Ok(());
+        }
+        Mode::Stats => {
+            print!("{}", stats(&games, &bags));
+            return Ok(());
         }
-        powersum += game.power();
+        Mode::Solve => (),
     }
 
-    println!("Part 1: {}", sum);
+    let mut sums = vec![0_u64; bags.len()];
+    let mut possible_ids = vec![Vec::new(); bags.len()];
+    let mut powersum: u64 = 0;
+
+    for game in &games {
+        for (i, bag) in bags.iter().enumerate() {
+            if game.possible(bag) {
+                sums[i] = sums[i]
+                    .checked_add(game.id as u64)
+                    .ok_or("Part 1 sum overflows u64")?;
+                possible_ids[i].push(game.id);
+            }
+        }
+        powersum = powersum
+            .checked_add(game.power(&palette)?)
+            .ok_or("Part 2 sum overflows u64")?;
+    }
+
+    if bags.len() == 1 {
+        println!("Part 1: {}", sums[0]);
+    } else {
+        for (i, bag) in bags.iter().enumerate() {
+            println!("Part 1 ({}): {} {:?}", bag, sums[i], possible_ids[i]);
+        }
+    }
     println!("Part 2: {}", powersum);
+
+    if options.min_bag {
+        let selected = match &options.games {
+            None => games.iter().collect::<Vec<&Game>>(),
+            Some(ids) => ids
+                .iter()
+                .map(|id| {
+                    games
+                        .iter()
+                        .find(|g| g.id == *id)
+                        .ok_or(format!("No game with id {}", id))
+                })
+                .collect::<Result<Vec<&Game>, _>>()?,
+        };
+        println!("Smallest bag: {}", min_bag(selected));
+    }
+
     Ok(())
 }
 
 mod tests {
     #[cfg(test)]
-    use super::{Game, Round};
+    use super::{
+        draw_sizes, ln_choose, min_bag, most_restrictive, palette, why_impossible, Color, Game,
+        Options, Round, DEFAULT_BAG,
+    };
+    #[cfg(test)]
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_round_parse() {
         let round = Round::parse("1 red, 2 green, 3 blue").unwrap();
-        assert_eq!(round.red, 1);
-        assert_eq!(round.green, 2);
-        assert_eq!(round.blue, 3);
+        assert_eq!(round.count(Color::intern("red")), 1);
+        assert_eq!(round.count(Color::intern("green")), 2);
+        assert_eq!(round.count(Color::intern("blue")), 3);
     }
 
     #[test]
@@ -135,8 +502,182 @@ mod tests {
         let game = Game::parse("Game 1: 1 red, 2 green, 3 blue; 4 red, 5 green, 6 blue").unwrap();
         assert_eq!(game.id, 1);
         assert_eq!(game.rounds.len(), 2);
-        assert_eq!(game.rounds[0].red, 1);
-        assert_eq!(game.rounds[0].green, 2);
-        assert_eq!(game.rounds[0].blue, 3);
+        assert_eq!(game.rounds[0].count(Color::intern("red")), 1);
+        assert_eq!(game.rounds[0].count(Color::intern("green")), 2);
+        assert_eq!(game.rounds[0].count(Color::intern("blue")), 3);
+    }
+
+    #[test]
+    fn test_arbitrary_colors() {
+        let game = Game::parse("Game 7: 2 purple, 1 red; 3 Purple, 2 blue; 1 orange").unwrap();
+        let purple = Color::intern("purple");
+        assert_eq!(game.min_round().count(purple), 3);
+        assert_eq!(
+            game.min_round().to_string(),
+            "1 red, 2 blue, 1 orange, 3 purple"
+        );
+        // Order doesn't depend on which color was interned first.
+        let round = Round::parse("1 zebra, 2 blue, 1 aardvark").unwrap();
+        assert_eq!(round.to_string(), "2 blue, 1 aardvark, 1 zebra");
+
+        let palette = game
+            .min_round()
+            .counts
+            .into_keys()
+            .collect::<BTreeSet<Color>>();
+        assert_eq!(game.power(&palette).unwrap(), 6);
+        assert!(!game.possible(&Round::parse("12 red, 13 green, 14 blue").unwrap()));
+        assert!(game.possible(&Round::parse("1 red, 2 blue, 3 purple, 1 orange").unwrap()));
+    }
+
+    #[test]
+    fn test_palette_keeps_original_colors() {
+        // A color never drawn still counts, making the power zero as in the original.
+        let games = vec![Game::parse("Game 1: 3 blue").unwrap()];
+        let colors = palette(&games);
+        assert_eq!(colors.len(), 3);
+        assert_eq!(games[0].power(&colors).unwrap(), 0);
+
+        let games = vec![Game::parse("Game 1: 3 blue, 1 red, 2 green, 2 teal").unwrap()];
+        assert_eq!(games[0].power(&palette(&games)).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_game_possible() {
+        let game = Game::parse("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green").unwrap();
+        assert!(!game.possible(&Round::parse(DEFAULT_BAG).unwrap()));
+        assert!(game.possible(&Round::parse("20 red, 13 green, 6 blue").unwrap()));
+    }
+
+    #[test]
+    fn test_min_bag() {
+        let games = [
+            Game::parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap(),
+            Game::parse("Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue")
+                .unwrap(),
+        ];
+        let expected = Round::parse("4 red, 3 green, 6 blue").unwrap();
+        assert_eq!(min_bag(&games), expected);
+        assert_eq!(
+            min_bag(&games[1..]),
+            Round::parse("1 red, 3 green, 4 blue").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_options_parse() {
+        let args = [
+            "--bag",
+            "1 red",
+            "--bag",
+            "2 blue",
+            "--min-bag",
+            "--games",
+            "1,3",
+        ]
+        .map(String::from);
+        let options = Options::parse(&args).unwrap();
+        assert_eq!(options.bags.len(), 2);
+        assert!(options.min_bag);
+        assert_eq!(options.games, Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn test_wide_counts() {
+        let round = Round::parse("200 red, 100 red, 4000000000 blue").unwrap();
+        assert_eq!(round.count(Color::intern("red")), 300);
+        assert_eq!(round.count(Color::intern("blue")), 4_000_000_000);
+
+        let game = Game::parse("Game 70000: 4000000000 red, 4000000000 green, 2 blue").unwrap();
+        let palette = game
+            .min_round()
+            .counts
+            .into_keys()
+            .collect::<BTreeSet<Color>>();
+        let err = game.power(&palette).unwrap_err();
+        assert_eq!(err.to_string(), "Game 70000: power overflows u64");
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        let err = Game::parse("Game 12: 1 red; 4000000000 blue, 300000000 blue").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Game 12, round 2: Too many blue cubes in one draw"
+        );
+
+        let err = Game::parse("Game 5: 1 red; 2 red; 99999999999 red").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Game 5, round 3: Invalid cube count"));
+    }
+
+    #[test]
+    fn test_why_impossible() {
+        let games = [
+            Game::parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap(),
+            Game::parse("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 15 red")
+                .unwrap(),
+        ];
+        let bags = [Round::parse(DEFAULT_BAG).unwrap()];
+        let palette = ["red", "green", "blue"].map(Color::intern).into();
+
+        let expected = "\
+Bag: 12 red, 13 green, 14 blue
+  Game 3: min round 20 red, 13 green, 6 blue (power 1560)
+    round 1: red 8 over
+    round 3: red 3 over
+";
+        assert_eq!(why_impossible(&games, &bags, &palette).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_draw_sizes() {
+        let games = [
+            Game::parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap(),
+            Game::parse("Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue")
+                .unwrap(),
+        ];
+        let sizes = draw_sizes(&games);
+        let red = &sizes[&Color::intern("red")];
+        assert_eq!(red.iter().collect::<Vec<_>>(), [(&1, &2), (&4, &1)]);
+        assert_eq!(sizes[&Color::intern("green")][&2], 3);
+
+        assert_eq!(
+            most_restrictive(&games, Color::intern("red")).unwrap().id,
+            1
+        );
+        assert_eq!(
+            most_restrictive(&games, Color::intern("green")).unwrap().id,
+            2
+        );
+    }
+
+    #[test]
+    fn test_likelihood() {
+        assert!((ln_choose(10, 3).exp() - 120.0).abs() < 1e-9);
+
+        // The series agrees with the exact product where both apply.
+        let exact: f64 = (1..=1500)
+            .map(|i| ((3000 - 1500 + i) as f64 / i as f64).ln())
+            .sum();
+        assert!((ln_choose(3000, 1500) - exact).abs() < 1e-9 * exact);
+
+        // Counts in the billions neither overflow nor take billions of steps.
+        let bag = Round::parse("4000000000 red, 4000000000 blue").unwrap();
+        let round = Round::parse("2000000000 red, 2000000000 blue").unwrap();
+        let p = round.likelihood(&bag);
+        assert!(p > 0.0 && p < 1.0);
+
+        let bag = Round::parse("2 red, 2 blue").unwrap();
+        let round = Round::parse("1 red, 1 blue").unwrap();
+        assert!((round.likelihood(&bag) - 4.0 / 6.0).abs() < 1e-12);
+
+        let game = Game::parse("Game 1: 1 red, 1 blue; 2 red").unwrap();
+        assert!((game.likelihood(&bag) - 4.0 / 6.0 / 6.0).abs() < 1e-12);
+        assert_eq!(
+            game.likelihood(&Round::parse("1 red, 2 blue").unwrap()),
+            0.0
+        );
     }
 }
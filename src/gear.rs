@@ -1,69 +1,551 @@
-use regex::Regex;
+use crate::grid::{Grid, Point};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
+use std::ops::Range;
 use std::result::Result;
 
-pub fn gear(f: File) -> Result<(), Box<dyn Error>> {
-    let reader = BufReader::new(f);
+/// A run of digits, as one `(row, cols)` span per row it covers.
+#[derive(Debug, PartialEq)]
+struct Number {
+    spans: Vec<(usize, Range<usize>)>,
+    value: u32,
+}
 
-    let schematic = reader
-        .lines()
-        .map(|l| l.map_err(|e| e.into()))
-        .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
+impl Number {
+    fn start(&self) -> Point {
+        let (row, cols) = &self.spans[0];
+        Point::new(*row, cols.start)
+    }
 
-    let mut adjacency_matrix: Vec<Vec<bool>> = Vec::new();
-    for _ in 0..schematic.len() {
-        adjacency_matrix.push(vec![false; schematic[0].len()]);
+    fn cells(&self) -> impl Iterator<Item = Point> + '_ {
+        self.spans
+            .iter()
+            .flat_map(|(row, cols)| cols.clone().map(|col| Point::new(*row, col)))
     }
+}
+
+/// Optional variations on how numbers and adjacency are read from a schematic. The
+/// default is the original puzzle's semantics.
+#[derive(Clone, Copy, Debug, Default)]
+struct Semantics {
+    /// Neighbours wrap around the grid edges as on a torus.
+    wrap: bool,
+    /// A number reaching the end of a row continues with the digits starting the next row.
+    multiline: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    at: Point,
+    kind: char,
+}
 
-    let symbol_pattern = Regex::new(r"[^\d\.]").unwrap();
+/// Every number and symbol in a schematic, with the adjacency edges between them.
+/// Numbers and symbols are referred to by their index, in reading order.
+struct PartIndex {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    symbol_ids: HashMap<Point, usize>,
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
+}
 
-    // Mark adjacency
-    for (i, row) in schematic.iter().enumerate() {
-        for m in symbol_pattern.find_iter(row) {
-            mark_adjacent(i as i16, m.start() as i16, &mut adjacency_matrix)
+impl PartIndex {
+    fn build(schematic: &Grid<char>, semantics: Semantics) -> Result<PartIndex, Box<dyn Error>> {
+        let symbols = schematic
+            .points()
+            .filter(|p| is_symbol(schematic[*p]))
+            .map(|at| Symbol {
+                at,
+                kind: schematic[at],
+            })
+            .collect::<Vec<Symbol>>();
+        let symbol_ids = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.at, i))
+            .collect::<HashMap<Point, usize>>();
+
+        let mut numbers = Vec::new();
+        let mut number_symbols = Vec::new();
+        let mut symbol_numbers = vec![Vec::new(); symbols.len()];
+
+        for spans in number_spans(schematic, semantics.multiline) {
+            let number = Number { spans, value: 0 };
+            let value = number
+                .cells()
+                .map(|p| schematic[p])
+                .collect::<String>()
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid number at {}: {}", number.start(), e))?;
+
+            let mut touching = number
+                .cells()
+                .flat_map(|p| -> Vec<Point> {
+                    if semantics.wrap {
+                        schematic.neighbours8_wrapping(p).collect()
+                    } else {
+                        schematic.neighbours8(p).collect()
+                    }
+                })
+                .filter_map(|p| symbol_ids.get(&p).copied())
+                .collect::<Vec<usize>>();
+            touching.sort();
+            touching.dedup();
+
+            for s in &touching {
+                symbol_numbers[*s].push(numbers.len());
+            }
+            number_symbols.push(touching);
+            numbers.push(Number { value, ..number });
         }
+
+        Ok(PartIndex {
+            numbers,
+            symbols,
+            symbol_ids,
+            number_symbols,
+            symbol_numbers,
+        })
+    }
+
+    fn symbol_at(&self, p: Point) -> Option<usize> {
+        self.symbol_ids.get(&p).copied()
+    }
+
+    /// Index of the number covering `p`, if any.
+    fn number_at(&self, p: Point) -> Option<usize> {
+        self.numbers.iter().position(|n| {
+            n.spans
+                .iter()
+                .any(|(row, cols)| *row == p.row && cols.contains(&p.col))
+        })
+    }
+
+    fn numbers_touching(&self, symbol: usize) -> impl Iterator<Item = &Number> {
+        self.symbol_numbers[symbol]
+            .iter()
+            .map(|n| &self.numbers[*n])
+    }
+
+    fn symbols_touching(&self, number: usize) -> impl Iterator<Item = &Symbol> {
+        self.number_symbols[number]
+            .iter()
+            .map(|s| &self.symbols[*s])
     }
 
-    let mut p1_result = 0;
+    /// Numbers that touch no symbol, i.e. are not part numbers.
+    fn isolated_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, s)| s.is_empty())
+            .map(|(n, _)| n)
+    }
+
+    /// Sum of the numbers touching at least one symbol.
+    fn part_sum(&self) -> u64 {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(n, _)| n.value as u64)
+            .sum()
+    }
+
+    /// For each symbol character, the sum of the numbers touching at least one symbol of
+    /// that character. A number touching symbols of several characters counts for each.
+    fn sums_by_symbol(&self) -> BTreeMap<char, u64> {
+        let mut sums = BTreeMap::new();
+        for (number, symbols) in self.numbers.iter().zip(&self.number_symbols) {
+            let mut kinds = symbols
+                .iter()
+                .map(|s| self.symbols[*s].kind)
+                .collect::<Vec<char>>();
+            kinds.sort();
+            kinds.dedup();
+
+            for kind in kinds {
+                *sums.entry(kind).or_insert(0) += number.value as u64;
+            }
+        }
+        sums
+    }
 
-    let num_pattern = Regex::new(r"(\d+)").unwrap();
-    for (i, row) in schematic.iter().enumerate() {
-        for m in num_pattern.find_iter(row) {
-            for j in m.start()..m.end() {
-                if adjacency_matrix[i][j] {
-                    p1_result += m.as_str().parse::<u32>().unwrap();
-                    break;
+    /// Indices of the `*` symbols touching exactly two numbers.
+    fn gears(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.symbols.len())
+            .filter(|s| self.symbols[*s].kind == '*' && self.symbol_numbers[*s].len() == 2)
+    }
+
+    fn gear_ratio_sum(&self) -> u64 {
+        self.gears()
+            .map(|s| {
+                self.numbers_touching(s)
+                    .map(|n| n.value as u64)
+                    .product::<u64>()
+            })
+            .sum()
+    }
+}
+
+/// Rows shown per page by `render --page`.
+const PAGE_ROWS: usize = 40;
+
+/// Cells shown on each side of the `render --at` point unless `--radius` says otherwise.
+const DEFAULT_RADIUS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Style {
+    Plain,
+    Counted,
+    Uncounted,
+    Gear,
+    Symbol,
+}
+
+impl Style {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Counted => "\x1b[32m",
+            Style::Uncounted => "\x1b[31m",
+            Style::Gear => "\x1b[1;33m",
+            Style::Symbol => "\x1b[2m",
+        }
+    }
+}
+
+/// Renders the `rows` x `cols` window of the schematic with ANSI colors: part numbers
+/// green, other numbers red, gears bold yellow and other symbols dimmed. Each line is
+/// prefixed with its row number.
+fn render(
+    schematic: &Grid<char>,
+    index: &PartIndex,
+    rows: Range<usize>,
+    cols: Range<usize>,
+) -> String {
+    let mut styles = schematic.map(|_| Style::Plain);
+    for (n, number) in index.numbers.iter().enumerate() {
+        let style = if index.number_symbols[n].is_empty() {
+            Style::Uncounted
+        } else {
+            Style::Counted
+        };
+        for p in number.cells() {
+            styles[p] = style;
+        }
+    }
+    for symbol in &index.symbols {
+        styles[symbol.at] = Style::Symbol;
+    }
+    for g in index.gears() {
+        styles[index.symbols[g].at] = Style::Gear;
+    }
+
+    let rows = rows.start..std::cmp::min(rows.end, schematic.height());
+    let cols = cols.start..std::cmp::min(cols.end, schematic.width());
+
+    let mut out = String::new();
+    for row in rows {
+        out += &format!("{:>4} ", row);
+        let mut current = Style::Plain;
+        for col in cols.clone() {
+            let p = Point::new(row, col);
+            if styles[p] != current {
+                if current != Style::Plain {
+                    out += "\x1b[0m";
                 }
+                out += styles[p].ansi();
+                current = styles[p];
+            }
+            out.push(schematic[p]);
+        }
+        if current != Style::Plain {
+            out += "\x1b[0m";
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Handles `render [--page N | --at row,col [--radius N]]`.
+fn render_mode(
+    schematic: &Grid<char>,
+    index: &PartIndex,
+    args: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let mut rows = 0..schematic.height();
+    let mut cols = 0..schematic.width();
+    let mut at = None;
+    let mut radius = DEFAULT_RADIUS;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--page" => {
+                let page = args
+                    .next()
+                    .ok_or("--page requires a number")?
+                    .parse::<usize>()?;
+                let start = page.saturating_mul(PAGE_ROWS);
+                rows = start..start.saturating_add(PAGE_ROWS);
             }
+            "--at" => at = Some(parse_point(args.next())?),
+            "--radius" => radius = args.next().ok_or("--radius requires a number")?.parse()?,
+            x => return Err(format!("Unknown render option: {}", x).into()),
         }
     }
 
-    println!("Part 1: {}", p1_result);
+    if let Some(p) = at {
+        // Window bounds saturate, and render clips them to the schematic anyway.
+        rows = p.row.saturating_sub(radius)..p.row.saturating_add(radius).saturating_add(1);
+        cols = p.col.saturating_sub(radius)..p.col.saturating_add(radius).saturating_add(1);
+    }
 
+    print!("{}", render(schematic, index, rows, cols));
     Ok(())
 }
 
-fn mark_adjacent(row: i16, col: i16, adjacency_matrix: &mut Vec<Vec<bool>>) {
-    let cells = [
-        (row - 1, col - 1),
-        (row - 1, col),
-        (row - 1, col + 1),
-        (row, col - 1),
-        (row, col + 1),
-        (row + 1, col - 1),
-        (row + 1, col),
-        (row + 1, col + 1),
-    ];
-
-    for cell in cells.iter() {
-        if cell.0 >= 0
-            && cell.1 >= 0
-            && cell.0 < adjacency_matrix.len() as i16
-            && cell.1 < adjacency_matrix[0].len() as i16
-        {
-            adjacency_matrix[cell.0 as usize][cell.1 as usize] = true;
+/// Parses a `row,col` command line argument.
+fn parse_point(arg: Option<&String>) -> Result<Point, Box<dyn Error>> {
+    let arg = arg.ok_or("expected a row,col point")?;
+    let (row, col) = arg
+        .split_once(',')
+        .ok_or(format!("Invalid point {}, expected row,col", arg))?;
+    Ok(Point::new(row.trim().parse()?, col.trim().parse()?))
+}
+
+/// Answers the `--symbol`, `--number` and `--isolated` queries against the index.
+fn query(index: &PartIndex, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--symbol" => {
+                let p = parse_point(args.next())?;
+                let s = index.symbol_at(p).ok_or(format!("No symbol at {}", p))?;
+                for n in index.numbers_touching(s) {
+                    println!("{} touches {} at {}", p, n.value, n.start());
+                }
+            }
+            "--number" => {
+                let p = parse_point(args.next())?;
+                let n = index.number_at(p).ok_or(format!("No number at {}", p))?;
+                for s in index.symbols_touching(n) {
+                    println!("{} touches {} at {}", index.numbers[n].value, s.kind, s.at);
+                }
+            }
+            "--isolated" => {
+                for n in index.isolated_numbers() {
+                    println!("{} at {} touches no symbol", n.value, n.start());
+                }
+            }
+            x => return Err(format!("Unknown gear option: {}", x).into()),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn gear(f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut text = String::new();
+    BufReader::new(f).read_to_string(&mut text)?;
+
+    let semantics = Semantics {
+        wrap: args.iter().any(|a| a == "--wrap"),
+        multiline: args.iter().any(|a| a == "--multiline"),
+    };
+    let args = args
+        .iter()
+        .filter(|a| *a != "--wrap" && *a != "--multiline")
+        .cloned()
+        .collect::<Vec<String>>();
+
+    let schematic = Grid::parse(&text)?;
+    let index = PartIndex::build(&schematic, semantics)?;
+
+    if args.first().map(String::as_str) == Some("render") {
+        return render_mode(&schematic, &index, &args[1..]);
+    }
+    if !args.is_empty() {
+        return query(&index, &args);
+    }
+
+    println!("Part 1: {}", index.part_sum());
+    println!("Part 2: {}", index.gear_ratio_sum());
+    for (kind, sum) in index.sums_by_symbol() {
+        println!("  {}: {}", kind, sum);
+    }
+
+    Ok(())
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii_digit() && c != '.'
+}
+
+/// Row and column ranges of every run of digits, in reading order. With `multiline`, a
+/// run ending a row and a run starting the next row form a single number.
+fn number_spans(schematic: &Grid<char>, multiline: bool) -> Vec<Vec<(usize, Range<usize>)>> {
+    let mut numbers: Vec<Vec<(usize, Range<usize>)>> = Vec::new();
+
+    for (row, cells) in schematic.rows().enumerate() {
+        let mut spans = Vec::new();
+        let mut start = None;
+        for (col, cell) in cells.iter().enumerate() {
+            match (cell.is_ascii_digit(), start) {
+                (true, None) => start = Some(col),
+                (false, Some(s)) => {
+                    spans.push(s..col);
+                    start = None;
+                }
+                _ => (),
+            }
         }
+        if let Some(s) = start {
+            spans.push(s..cells.len());
+        }
+
+        for cols in spans {
+            let continues = numbers.last().and_then(|n| n.last()).is_some_and(|(r, c)| {
+                multiline && cols.start == 0 && *r + 1 == row && c.end == cells.len()
+            });
+            match numbers.last_mut() {
+                Some(number) if continues => number.push((row, cols)),
+                _ => numbers.push(vec![(row, cols)]),
+            }
+        }
+    }
+
+    numbers
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    const EXAMPLE: &str = "\
+467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+    #[test]
+    fn test_part_sums() {
+        let index = PartIndex::build(&Grid::parse(EXAMPLE).unwrap(), Semantics::default()).unwrap();
+        assert_eq!(index.part_sum(), 4361);
+        assert_eq!(index.gear_ratio_sum(), 467835);
+    }
+
+    #[test]
+    fn test_part_index_queries() {
+        let index = PartIndex::build(&Grid::parse(EXAMPLE).unwrap(), Semantics::default()).unwrap();
+
+        let star = index.symbol_at(Point::new(1, 3)).unwrap();
+        let touching = index
+            .numbers_touching(star)
+            .map(|n| n.value)
+            .collect::<Vec<u32>>();
+        assert_eq!(touching, [467, 35]);
+
+        let n617 = index.numbers.iter().position(|n| n.value == 617).unwrap();
+        let symbols = index.symbols_touching(n617).collect::<Vec<&Symbol>>();
+        assert_eq!(
+            symbols,
+            [&Symbol {
+                at: Point::new(4, 3),
+                kind: '*'
+            }]
+        );
+
+        let isolated = index
+            .isolated_numbers()
+            .map(|n| n.value)
+            .collect::<Vec<u32>>();
+        assert_eq!(isolated, [114, 58]);
+    }
+
+    #[test]
+    fn test_sums_by_symbol() {
+        let index = PartIndex::build(&Grid::parse(EXAMPLE).unwrap(), Semantics::default()).unwrap();
+        let sums = index.sums_by_symbol();
+        assert_eq!(sums[&'#'], 633);
+        assert_eq!(sums[&'$'], 664);
+        assert_eq!(sums[&'+'], 592);
+        assert_eq!(sums[&'*'], 467 + 35 + 617 + 755 + 598);
+    }
+
+    #[test]
+    fn test_render() {
+        let schematic = Grid::parse(EXAMPLE).unwrap();
+        let index = PartIndex::build(&schematic, Semantics::default()).unwrap();
+
+        let top = render(&schematic, &index, 0..2, 0..10);
+        assert_eq!(
+            top,
+            "   0 \x1b[32m467\x1b[0m..\x1b[31m114\x1b[0m..\n   1 ...\x1b[1;33m*\x1b[0m......\n"
+        );
+
+        let window = render(&schematic, &index, 3..4, 5..20);
+        assert_eq!(window, "   3 .\x1b[2m#\x1b[0m...\n");
+    }
+
+    #[test]
+    fn test_default_semantics_on_input() {
+        let text = std::fs::read_to_string("03 - Gear Ratios/input.txt").unwrap();
+        let schematic = Grid::parse(&text).unwrap();
+        let index = PartIndex::build(&schematic, Semantics::default()).unwrap();
+        assert_eq!(index.part_sum(), 512794);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let schematic = Grid::parse("12...\n.....\n....*").unwrap();
+        let plain = PartIndex::build(&schematic, Semantics::default()).unwrap();
+        assert_eq!(plain.part_sum(), 0);
+
+        let wrap = Semantics {
+            wrap: true,
+            ..Semantics::default()
+        };
+        let wrapped = PartIndex::build(&schematic, wrap).unwrap();
+        assert_eq!(wrapped.part_sum(), 12);
+    }
+
+    #[test]
+    fn test_multiline() {
+        let schematic = Grid::parse("...12\n34*..\n.....\n....5\n.6...").unwrap();
+        let plain = PartIndex::build(&schematic, Semantics::default()).unwrap();
+        assert_eq!(plain.part_sum(), 46);
+        assert_eq!(plain.gear_ratio_sum(), 12 * 34);
+
+        let multiline = Semantics {
+            multiline: true,
+            ..Semantics::default()
+        };
+        let joined = PartIndex::build(&schematic, multiline).unwrap();
+        assert_eq!(joined.part_sum(), 1234);
+        assert_eq!(joined.gear_ratio_sum(), 0);
+        assert_eq!(
+            joined.number_at(Point::new(1, 0)),
+            joined.number_at(Point::new(0, 4))
+        );
+        assert_eq!(
+            joined
+                .isolated_numbers()
+                .map(|n| n.value)
+                .collect::<Vec<u32>>(),
+            [5, 6]
+        );
     }
 }
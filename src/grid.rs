@@ -0,0 +1,326 @@
+// Shared by the grid puzzles. Items no puzzle uses yet are marked `allow(dead_code)`.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::result::Result;
+
+#[allow(dead_code)]
+const OFFSETS_4: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+const OFFSETS_8: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Point {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Point {
+    pub fn new(row: usize, col: usize) -> Point {
+        Point { row, col }
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.row, self.col)
+    }
+}
+
+/// Rectangular, row-major grid of cells addressed by `Point`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    #[allow(dead_code)]
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    /// Builds a grid from rows of cells, rejecting rows of differing lengths.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>, Box<dyn Error>> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |r| r.len());
+
+        let mut cells = Vec::with_capacity(width * height);
+        for (i, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                return Err(format!(
+                    "Ragged grid: row {} has {} cells, expected {}",
+                    i,
+                    row.len(),
+                    width
+                )
+                .into());
+            }
+            cells.extend(row);
+        }
+
+        Ok(Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.row < self.height && p.col < self.width
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        if self.contains(p) {
+            Some(&self.cells[p.row * self.width + p.col])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        if self.contains(p) {
+            Some(&mut self.cells[p.row * self.width + p.col])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(|r| self.row(r))
+    }
+
+    #[allow(dead_code)]
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> {
+        (0..self.height).map(move |r| &self[Point::new(r, col)])
+    }
+
+    /// Every point in row-major order.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let width = self.width;
+        (0..self.height).flat_map(move |r| (0..width).map(move |c| Point::new(r, c)))
+    }
+
+    /// Points of the rectangle spanned by the two corners, inclusive, clipped to the grid.
+    #[allow(dead_code)]
+    pub fn region(&self, top_left: Point, bottom_right: Point) -> impl Iterator<Item = Point> {
+        let rows = top_left.row..std::cmp::min(bottom_right.row + 1, self.height);
+        let cols = top_left.col..std::cmp::min(bottom_right.col + 1, self.width);
+        rows.flat_map(move |r| cols.clone().map(move |c| Point::new(r, c)))
+    }
+
+    /// Orthogonal neighbours of `p` that lie inside the grid.
+    #[allow(dead_code)]
+    pub fn neighbours4(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.offset_points(p, &OFFSETS_4)
+    }
+
+    /// Orthogonal and diagonal neighbours of `p` that lie inside the grid.
+    pub fn neighbours8(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.offset_points(p, &OFFSETS_8)
+    }
+
+    /// Orthogonal neighbours of `p`, wrapping around the grid edges as on a torus.
+    #[allow(dead_code)]
+    pub fn neighbours4_wrapping(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.wrapping_offset_points(p, &OFFSETS_4)
+    }
+
+    /// Orthogonal and diagonal neighbours of `p`, wrapping around the grid edges as on a
+    /// torus. On grids narrower or shorter than three cells a neighbour may repeat or be
+    /// `p` itself.
+    pub fn neighbours8_wrapping(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.wrapping_offset_points(p, &OFFSETS_8)
+    }
+
+    fn wrapping_offset_points(
+        &self,
+        p: Point,
+        offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = Point> + '_ {
+        offsets.iter().map(move |(dr, dc)| {
+            Point::new(
+                (p.row as isize + dr).rem_euclid(self.height as isize) as usize,
+                (p.col as isize + dc).rem_euclid(self.width as isize) as usize,
+            )
+        })
+    }
+
+    fn offset_points(
+        &self,
+        p: Point,
+        offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = Point> + '_ {
+        offsets.iter().filter_map(move |(dr, dc)| {
+            let n = Point::new(
+                p.row.checked_add_signed(*dr)?,
+                p.col.checked_add_signed(*dc)?,
+            );
+            self.contains(n).then_some(n)
+        })
+    }
+
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl Grid<char> {
+    /// Parses one row per line of `text`, ignoring trailing empty lines.
+    pub fn parse(text: &str) -> Result<Grid<char>, Box<dyn Error>> {
+        let mut rows: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        while rows.last().is_some_and(|r| r.is_empty()) {
+            rows.pop();
+        }
+        Grid::from_rows(rows)
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, p: Point) -> &T {
+        self.get(p)
+            .unwrap_or_else(|| panic!("{} is outside the grid", p))
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        self.get_mut(p)
+            .unwrap_or_else(|| panic!("{} is outside the grid", p))
+    }
+}
+
+/// Renders one line per row, the inverse of `Grid::parse` for `Grid<char>`.
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let text = "ab.\n.#c\n";
+        let grid = Grid::parse(text).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid[Point::new(1, 1)], '#');
+        assert_eq!(grid.column(2).collect::<String>(), ".c");
+        assert_eq!(grid.to_string(), text);
+    }
+
+    #[test]
+    fn test_trailing_blank_lines() {
+        let grid = Grid::parse("ab.\n.#c\n\n\n").unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert!(Grid::parse("ab.\n\n.#c").is_err());
+    }
+
+    #[test]
+    fn test_ragged() {
+        let err = Grid::parse("abc\nde\nfgh").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Ragged grid: row 1 has 2 cells, expected 3"
+        );
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let grid = Grid::new(3, 3, 0);
+        let corner = grid.neighbours8(Point::new(0, 0)).collect::<Vec<Point>>();
+        assert_eq!(
+            corner,
+            [Point::new(0, 1), Point::new(1, 0), Point::new(1, 1)]
+        );
+        assert_eq!(grid.neighbours8(Point::new(1, 1)).count(), 8);
+        assert_eq!(grid.neighbours4(Point::new(1, 1)).count(), 4);
+        assert_eq!(grid.neighbours4(Point::new(2, 1)).count(), 3);
+    }
+
+    #[test]
+    fn test_region() {
+        let grid = Grid::new(4, 3, 0);
+        let region = grid
+            .region(Point::new(1, 2), Point::new(5, 5))
+            .collect::<Vec<Point>>();
+        assert_eq!(
+            region,
+            [
+                Point::new(1, 2),
+                Point::new(1, 3),
+                Point::new(2, 2),
+                Point::new(2, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrapping_neighbours() {
+        let grid = Grid::new(4, 3, 0);
+        let corner = grid
+            .neighbours8_wrapping(Point::new(0, 0))
+            .collect::<Vec<Point>>();
+        assert_eq!(corner.len(), 8);
+        assert!(corner.contains(&Point::new(2, 3)));
+        assert!(corner.contains(&Point::new(0, 3)));
+        assert!(corner.contains(&Point::new(2, 0)));
+
+        let edge = grid
+            .neighbours4_wrapping(Point::new(1, 3))
+            .collect::<Vec<Point>>();
+        assert_eq!(
+            edge,
+            [
+                Point::new(0, 3),
+                Point::new(1, 2),
+                Point::new(1, 0),
+                Point::new(2, 3)
+            ]
+        );
+    }
+}
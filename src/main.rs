@@ -3,10 +3,12 @@ use std::error::Error;
 use std::fs::File;
 use std::path::PathBuf;
 
+mod bigint;
 mod boat_race;
 mod camel_cards;
 mod cube;
 mod gear;
+mod grid;
 mod mirage;
 mod scratchcard;
 mod seed;
@@ -16,9 +18,9 @@ mod wasteland;
 const INPUT_FILE_NAME: &str = "input.txt";
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let day_subdir = env::args()
-        .nth(1)
-        .ok_or("Provide a day subdirectory")?;
+    let args: Vec<String> = env::args().collect();
+    let day_subdir = args.get(1).ok_or("Provide a day subdirectory")?;
+    let options = &args[2..];
     let input_path = PathBuf::from(day_subdir).join(INPUT_FILE_NAME);
     let input_file = File::open(&input_path).map_err(|_| "Could not open input file")?;
 
@@ -30,14 +32,14 @@ fn main() -> Result<(), Box<dyn Error>> {
         .get(0..2)
         .ok_or("Could not get day from subdir name")?;
 
-    return match day {
+    match day {
         "01" => trebuchet::trebuchet(input_file),
-        "02" => cube::cube(input_file),
-        "03" => gear::gear(input_file),
-        "04" => scratchcard::scratchcard(input_file),
-        "05" => seed::seed(input_file),
-        "06" => boat_race::boat_race(input_file),
-        "07" => camel_cards::camel_cards(input_file),
+        "02" => cube::cube(input_file, options),
+        "03" => gear::gear(input_file, options),
+        "04" => scratchcard::scratchcard(input_file, options),
+        "05" => seed::seed(input_file, options),
+        "06" => boat_race::boat_race(input_file, options),
+        "07" => camel_cards::camel_cards(input_file, options),
         "08" => wasteland::wasteland(input_file),
         "09" => mirage::mirage(input_file),
         _ => Err(format!("Day {} not implemented", day).into()),
@@ -28,15 +28,15 @@ pub fn mirage(f: File) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn differences(nums: &Vec<i64>) -> Vec<i64> {
+fn differences(nums: &[i64]) -> Vec<i64> {
     nums.iter()
         .zip(nums[1..].iter())
         .map(|(a, b)| b - a)
         .collect()
 }
 
-fn predict_next(nums: &Vec<i64>) -> Result<i64, Box<dyn Error>> {
-    let mut cur = nums.clone();
+fn predict_next(nums: &[i64]) -> Result<i64, Box<dyn Error>> {
+    let mut cur = nums.to_vec();
     let mut last_diffs: Vec<i64> = Vec::new();
 
     let Some(ln) = nums.iter().last() else {
@@ -46,7 +46,7 @@ fn predict_next(nums: &Vec<i64>) -> Result<i64, Box<dyn Error>> {
     loop {
         let d = differences(&cur);
         if d.iter().all(|d| *d == 0) {
-            return Ok(last_diffs.iter().rev().fold(0, |acc, d| acc + d) + ln);
+            return Ok(last_diffs.iter().rev().sum::<i64>() + ln);
         }
 
         let Some(ld) = d.last() else {
@@ -58,8 +58,8 @@ fn predict_next(nums: &Vec<i64>) -> Result<i64, Box<dyn Error>> {
     }
 }
 
-fn predict_prev(nums: &Vec<i64>) -> Result<i64, Box<dyn Error>> {
-    let mut cur = nums.clone();
+fn predict_prev(nums: &[i64]) -> Result<i64, Box<dyn Error>> {
+    let mut cur = nums.to_vec();
     let mut first_diffs: Vec<i64> = Vec::new();
 
     let Some(fst) = nums.iter().next() else {
@@ -72,7 +72,7 @@ fn predict_prev(nums: &Vec<i64>) -> Result<i64, Box<dyn Error>> {
             return Ok(fst - first_diffs.iter().rev().fold(0, |acc, d| d - acc));
         }
 
-        let Some(ld) = d.iter().next() else {
+        let Some(ld) = d.first() else {
             return Err("empty diffs".into());
         };
 
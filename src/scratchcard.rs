@@ -1,86 +1,567 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 use std::result::Result;
 
+#[derive(Clone)]
 struct ScratchCard {
-    id: u16,
-    score: u32,
+    id: u32,
+    winning: Vec<u32>,
+    drawn: Vec<u32>,
+    matches: usize,
+}
+
+/// Where one card's copies came from in the part 2 cascade.
+#[derive(Debug, PartialEq)]
+struct CardTrace {
+    id: u32,
+    matches: usize,
+    copies: u64,
+    /// Earlier cards that won copies of this one, with how many copies each gave.
+    contributors: Vec<(u32, u64)>,
+}
+
+impl CardTrace {
+    const CSV_HEADER: &'static str = "id,matches,copies,contributors";
+
+    /// One CSV row, with contributors as `id:copies` pairs separated by `;`.
+    fn csv(&self) -> String {
+        let contributors = self
+            .contributors
+            .iter()
+            .map(|(id, n)| format!("{}:{}", id, n))
+            .collect::<Vec<String>>();
+        format!(
+            "{},{},{},{}",
+            self.id,
+            self.matches,
+            self.copies,
+            contributors.join(";")
+        )
+    }
+}
+
+enum Mode {
+    Solve,
+    Trace,
+    WhatIf { id: u32, matches: usize },
+}
+
+struct Options {
+    mode: Mode,
+    /// Print the trace as CSV. `--csv` on its own also selects the trace.
+    csv: bool,
+    scoring: Scoring,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, Box<dyn Error>> {
+        let mut options = Options {
+            mode: Mode::Solve,
+            csv: false,
+            scoring: Scoring::Doubling,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scoring" => {
+                    let rule = args.next().ok_or(
+                        "--scoring requires doubling, linear, fibonacci or table:<points>,...",
+                    )?;
+                    options.scoring = Scoring::parse(rule)?;
+                }
+                "trace" => options.mode = Mode::Trace,
+                "--csv" => options.csv = true,
+                "what-if" => {
+                    let id = args.next().ok_or("what-if requires a card id")?.parse()?;
+                    let matches = args
+                        .next()
+                        .ok_or("what-if requires a match count")?
+                        .parse()?;
+                    options.mode = Mode::WhatIf { id, matches };
+                }
+                x => return Err(format!("Unknown scratchcard option: {}", x).into()),
+            }
+        }
+
+        if options.csv {
+            if let Mode::Solve = options.mode {
+                options.mode = Mode::Trace;
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// How many points a card is worth for its number of matches.
+#[derive(Debug, PartialEq)]
+enum Scoring {
+    /// 1 point for the first match, doubled for each further match.
+    Doubling,
+    /// 1 point per match.
+    Linear,
+    /// The nth Fibonacci number for n matches: 1, 1, 2, 3, 5, ...
+    Fibonacci,
+    /// Points looked up by number of matches, starting at 0 matches.
+    Table(Vec<u128>),
+}
+
+impl Scoring {
+    /// Parses `doubling`, `linear`, `fibonacci` or `table:<points>,<points>,...`.
+    fn parse(s: &str) -> Result<Scoring, Box<dyn Error>> {
+        match s {
+            "doubling" => Ok(Scoring::Doubling),
+            "linear" => Ok(Scoring::Linear),
+            "fibonacci" => Ok(Scoring::Fibonacci),
+            _ => {
+                let table = s
+                    .strip_prefix("table:")
+                    .ok_or(format!("Unknown scoring rule: {}", s))?
+                    .split(',')
+                    .map(|n| n.trim().parse::<u128>())
+                    .collect::<Result<Vec<u128>, _>>()?;
+                Ok(Scoring::Table(table))
+            }
+        }
+    }
+
+    /// Points for `matches` matches, or `None` if they overflow a `u128` or fall outside
+    /// the table.
+    fn score(&self, matches: usize) -> Option<u128> {
+        match (self, matches) {
+            (Scoring::Table(t), n) => t.get(n).copied(),
+            (_, 0) => Some(0),
+            (Scoring::Doubling, n) => 1_u128.checked_shl(u32::try_from(n - 1).ok()?),
+            (Scoring::Linear, n) => u128::try_from(n).ok(),
+            (Scoring::Fibonacci, n) => {
+                let (mut a, mut b) = (0_u128, 1_u128);
+                for _ in 1..n {
+                    (a, b) = (b, a.checked_add(b)?);
+                }
+                Some(b)
+            }
+        }
+    }
 }
 
 impl ScratchCard {
-    fn parse(line: &String) -> Result<ScratchCard, Box<dyn Error>> {
-        let parts = line.split(':').collect::<Vec<&str>>();
-        let numbers = parts.get(1).ok_or("Invalid line format")?;
-        let series = numbers.split('|').collect::<Vec<&str>>();
-
-        let id = parts[0]
-            .split_whitespace()
-            .nth(1)
-            .ok_or("Invalid line format")
-            .map(|n| n.parse::<u16>())??;
-
-        let win_nums = series[0]
-            .split_whitespace()
-            .map(|n| n.parse::<u8>().unwrap())
-            .collect::<HashSet<u8>>();
-
-        let nums = series[1]
-            .split_whitespace()
-            .map(|n| n.parse::<u8>().unwrap())
-            .collect::<HashSet<u8>>();
-
-        let winners = win_nums.intersection(&nums).count() as u32;
-
-        let score = match winners {
-            0 => 0,
-            n => 2_i32.pow(n - 1) as u32,
+    fn parse(line: &str) -> Result<ScratchCard, Box<dyn Error>> {
+        let (label, numbers) = line.split_once(':').ok_or("missing ':' after card id")?;
+
+        let id = match label.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["Card", id] => id
+                .parse::<u32>()
+                .map_err(|e| format!("invalid card id {}: {}", id, e))?,
+            _ => return Err(format!("invalid card label: {}", label).into()),
         };
 
-        Ok(ScratchCard {
+        let (winning, drawn) = numbers
+            .split_once('|')
+            .ok_or("missing '|' between winning and drawn numbers")?;
+        if drawn.contains('|') {
+            return Err("more than one '|' in card".into());
+        }
+
+        let winning = parse_numbers(winning)?;
+        let drawn = parse_numbers(drawn)?;
+
+        let mut card = ScratchCard {
             id,
-            score,
+            winning,
+            drawn,
+            matches: 0,
+        };
+        card.matches = card.matched_numbers().len();
+
+        Ok(card)
+    }
+
+    /// Drawn numbers that are also winning numbers, each counted once.
+    fn matched_numbers(&self) -> HashSet<u32> {
+        self.drawn
+            .iter()
+            .filter(|n| self.winning.contains(n))
+            .copied()
+            .collect()
+    }
+
+    /// Warnings for numbers repeated within the winning or the drawn series. Repeats are
+    /// harmless for matching, which counts each number once, but usually mean a typo.
+    fn duplicate_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (name, series) in [("winning", &self.winning), ("drawn", &self.drawn)] {
+            let mut seen = HashSet::new();
+            let mut reported = HashSet::new();
+            for n in series {
+                if !seen.insert(n) && reported.insert(n) {
+                    warnings.push(format!(
+                        "Card {}: {} number {} appears more than once",
+                        self.id, name, n
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    fn score(&self, scoring: &Scoring) -> Result<u128, Box<dyn Error>> {
+        scoring.score(self.matches).ok_or(
+            format!(
+                "Card {} with {} matches has no {:?} score",
+                self.id, self.matches, scoring
+            )
+            .into(),
+        )
+    }
+}
+
+fn parse_numbers(series: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    series
+        .split_whitespace()
+        .map(|n| {
+            n.parse::<u32>()
+                .map_err(|e| format!("invalid number {}: {}", n, e).into())
         })
+        .collect()
+}
+
+/// Checks that `cards`, sorted by id, have unique and contiguous ids, since the copy
+/// rules hand out copies by id.
+fn check_ids(cards: &[ScratchCard]) -> Result<(), Box<dyn Error>> {
+    for pair in cards.windows(2) {
+        let (prev, next) = (pair[0].id, pair[1].id);
+        if next == prev {
+            return Err(format!("Duplicate card id {}", next).into());
+        }
+        if next != prev + 1 {
+            return Err(format!("Card ids jump from {} to {}", prev, next).into());
+        }
     }
+    Ok(())
 }
 
-pub fn scratchcard(f: File) -> Result<(), Box<dyn Error>> {
+pub fn scratchcard(f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
     let reader = BufReader::new(f);
 
+    let options = Options::parse(args)?;
+
     let lines = reader
         .lines()
         .map(|l| l.map_err(|e| e.into()))
         .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
 
-    let cardmap = lines.iter().fold(HashMap::new(), |mut acc, line| {
-        let card = ScratchCard::parse(line).unwrap();
-        acc.insert(card.id, card);
-        acc
-    });
+    let mut cards = lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| ScratchCard::parse(l).map_err(|e| format!("Line {}: {}", i + 1, e).into()))
+        .collect::<Result<Vec<ScratchCard>, Box<dyn Error>>>()?;
+    cards.sort_by_key(|c| c.id);
+    check_ids(&cards)?;
+
+    for warning in cards.iter().flat_map(|c| c.duplicate_warnings()) {
+        eprintln!("Warning: {}", warning);
+    }
+
+    match options.mode {
+        Mode::Solve => (),
+        Mode::Trace => {
+            let traces = trace(&cards)?;
+            if options.csv {
+                println!("{}", CardTrace::CSV_HEADER);
+                traces.iter().for_each(|t| println!("{}", t.csv()));
+            } else {
+                for t in traces {
+                    let contributors = t
+                        .contributors
+                        .iter()
+                        .map(|(id, n)| format!("{} from card {}", n, id))
+                        .collect::<Vec<String>>();
+                    println!(
+                        "Card {}: {} matches, {} copies (1 original, {})",
+                        t.id,
+                        t.matches,
+                        t.copies,
+                        if contributors.is_empty() {
+                            "none won".to_string()
+                        } else {
+                            contributors.join(", ")
+                        }
+                    );
+                }
+            }
+            return Ok(());
+        }
+        Mode::WhatIf { id, matches } => {
+            println!(
+                "Part 2 with card {} at {} matches: {}",
+                id,
+                matches,
+                what_if(&cards, id, matches)?
+            );
+            return Ok(());
+        }
+    }
 
-    let score = cardmap.values().fold(0, |acc, card| acc + card.score);
-    let cards_won = cardmap.keys().fold(0, |acc, id| {
-        acc + card_wins(&cardmap, id, 1)
-    });
+    let score = cards.iter().try_fold(0_u128, |acc, card| {
+        acc.checked_add(card.score(&options.scoring)?)
+            .ok_or_else(|| Box::<dyn Error>::from("Part 1 score overflows u128"))
+    })?;
+    let cards_won = copies(&cards)?.iter().sum::<u64>();
 
     println!("Part 1: {}", score);
     println!("Part 2: {}", cards_won);
     Ok(())
 }
 
-fn card_wins(cards: &HashMap<u16, ScratchCard>, id: &u16, memo: u32) -> u32 {
-    match cards.get(&id) {
-        None => return memo,
-        Some(card) => {
-            if card.score == 0 {
-                return memo;
+/// Number of copies of each card, original included, once every win has been cashed in.
+/// Cards must be in id order; wins past the last card are dropped.
+///
+/// Each card's copies are added to the range of cards it wins, so this tracks the
+/// copies still being handed out and when each contribution expires, in a single pass.
+fn copies(cards: &[ScratchCard]) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut copies = Vec::with_capacity(cards.len());
+    let mut expiring = vec![0_u64; cards.len() + 1];
+    let mut running: u64 = 0;
+
+    for (i, card) in cards.iter().enumerate() {
+        running -= expiring[i];
+        let count = running
+            .checked_add(1)
+            .ok_or(format!("Copy count overflows u64 at card {}", card.id))?;
+        copies.push(count);
+
+        let end = std::cmp::min((i + 1).saturating_add(card.matches), cards.len());
+        if end > i + 1 {
+            running = running
+                .checked_add(count)
+                .ok_or(format!("Copy count overflows u64 at card {}", card.id))?;
+            expiring[end] += count;
+        }
+    }
+
+    Ok(copies)
+}
+
+/// Per-card breakdown of the part 2 cascade: match count, final copies, and the earlier
+/// cards that won each copy.
+fn trace(cards: &[ScratchCard]) -> Result<Vec<CardTrace>, Box<dyn Error>> {
+    let copies = copies(cards)?;
+    let mut traces = cards
+        .iter()
+        .zip(&copies)
+        .map(|(card, n)| CardTrace {
+            id: card.id,
+            matches: card.matches,
+            copies: *n,
+            contributors: Vec::new(),
+        })
+        .collect::<Vec<CardTrace>>();
+
+    for (i, card) in cards.iter().enumerate() {
+        let end = std::cmp::min((i + 1).saturating_add(card.matches), cards.len());
+        for t in &mut traces[i + 1..end] {
+            t.contributors.push((card.id, copies[i]));
+        }
+    }
+
+    Ok(traces)
+}
+
+/// Part 2 total if card `id` had `matches` matches instead.
+fn what_if(cards: &[ScratchCard], id: u32, matches: usize) -> Result<u64, Box<dyn Error>> {
+    let mut cards = cards.to_vec();
+    let card = cards
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or(format!("No card with id {}", id))?;
+    card.matches = matches;
+
+    Ok(copies(&cards)?.iter().sum())
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    const EXAMPLE: [&str; 6] = [
+        "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53",
+        "Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19",
+        "Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1",
+        "Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83",
+        "Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36",
+        "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11",
+    ];
+
+    #[test]
+    fn test_copies() {
+        let cards = EXAMPLE
+            .iter()
+            .map(|l| ScratchCard::parse(l).unwrap())
+            .collect::<Vec<ScratchCard>>();
+
+        let score = |c: &ScratchCard| c.score(&Scoring::Doubling).unwrap();
+        assert_eq!(cards.iter().map(score).sum::<u128>(), 13);
+        assert_eq!(copies(&cards).unwrap(), [1, 2, 4, 8, 14, 1]);
+    }
+
+    #[test]
+    fn test_copies_long_chain() {
+        let cards = (1..=50_000)
+            .map(|id| ScratchCard {
+                id,
+                winning: Vec::new(),
+                drawn: Vec::new(),
+                matches: 3,
+            })
+            .collect::<Vec<ScratchCard>>();
+        assert!(copies(&cards).is_err());
+
+        let cards = (1..=50_000)
+            .map(|id| ScratchCard {
+                id,
+                winning: Vec::new(),
+                drawn: Vec::new(),
+                matches: 1,
+            })
+            .collect::<Vec<ScratchCard>>();
+        let copies = copies(&cards).unwrap();
+        assert_eq!(copies[49_999], 50_000);
+        assert_eq!(copies.iter().sum::<u64>(), 50_000 * 50_001 / 2);
+    }
+
+    #[test]
+    fn test_scoring() {
+        let card = ScratchCard::parse("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53").unwrap();
+        assert_eq!(card.winning, [41, 48, 83, 86, 17]);
+        assert_eq!(card.drawn.len(), 8);
+        assert_eq!(card.matches, 4);
+
+        assert_eq!(card.score(&Scoring::Doubling).unwrap(), 8);
+        assert_eq!(card.score(&Scoring::Linear).unwrap(), 4);
+        assert_eq!(card.score(&Scoring::Fibonacci).unwrap(), 3);
+        let table = Scoring::parse("table:0,1,3,6,10").unwrap();
+        assert_eq!(card.score(&table).unwrap(), 10);
+        assert!(card.score(&Scoring::parse("table:0,1").unwrap()).is_err());
+
+        assert_eq!(Scoring::Doubling.score(0), Some(0));
+        assert_eq!(Scoring::Doubling.score(128), Some(1 << 127));
+        assert_eq!(Scoring::Doubling.score(129), None);
+        assert_eq!(
+            Scoring::Fibonacci.score(186),
+            Some(332825110087067562321196029789634457848)
+        );
+        assert_eq!(Scoring::Fibonacci.score(187), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("Card 1 41 48 | 83 86", "missing ':' after card id"),
+            (
+                "Card 1: 41 48 83 86",
+                "missing '|' between winning and drawn numbers",
+            ),
+            ("Card 1: 41 | 48 | 83", "more than one '|' in card"),
+            (
+                "Card x: 41 | 48",
+                "invalid card id x: invalid digit found in string",
+            ),
+            ("Game 1: 41 | 48", "invalid card label: Game 1"),
+            (
+                "Card 1: 41 4a | 48",
+                "invalid number 4a: invalid digit found in string",
+            ),
+        ];
+
+        for (line, expected) in cases {
+            let err = ScratchCard::parse(line).err().unwrap();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        let card = ScratchCard::parse("Card 1: 1000 17 | 1000 300").unwrap();
+        assert_eq!(card.matches, 1);
+    }
+
+    #[test]
+    fn test_validation() {
+        let card = ScratchCard::parse("Card 3: 5 5 7 5 | 7 8 8").unwrap();
+        assert_eq!(card.matches, 1);
+        assert_eq!(
+            card.duplicate_warnings(),
+            [
+                "Card 3: winning number 5 appears more than once",
+                "Card 3: drawn number 8 appears more than once"
+            ]
+        );
+
+        let cards = |ids: &[u32]| {
+            ids.iter()
+                .map(|id| ScratchCard::parse(&format!("Card {}: 1 | 2", id)).unwrap())
+                .collect::<Vec<ScratchCard>>()
+        };
+        assert!(check_ids(&cards(&[4, 5, 6])).is_ok());
+        let err = check_ids(&cards(&[1, 2, 2, 3])).unwrap_err();
+        assert_eq!(err.to_string(), "Duplicate card id 2");
+        let err = check_ids(&cards(&[1, 2, 4])).unwrap_err();
+        assert_eq!(err.to_string(), "Card ids jump from 2 to 4");
+    }
+
+    #[test]
+    fn test_trace() {
+        let cards = EXAMPLE
+            .iter()
+            .map(|l| ScratchCard::parse(l).unwrap())
+            .collect::<Vec<ScratchCard>>();
+
+        let traces = trace(&cards).unwrap();
+        assert_eq!(
+            traces[4],
+            CardTrace {
+                id: 5,
+                matches: 0,
+                copies: 14,
+                contributors: vec![(1, 1), (3, 4), (4, 8)],
             }
+        );
+        assert_eq!(traces[3].csv(), "4,1,8,1:1;2:2;3:4");
+        assert_eq!(traces[0].csv(), "1,4,1,");
+    }
+
+    #[test]
+    fn test_what_if() {
+        let cards = EXAMPLE
+            .iter()
+            .map(|l| ScratchCard::parse(l).unwrap())
+            .collect::<Vec<ScratchCard>>();
+
+        assert_eq!(what_if(&cards, 1, 4).unwrap(), 30);
+        assert_eq!(what_if(&cards, 1, 0).unwrap(), 1 + 1 + 2 + 4 + 7 + 1);
+        assert_eq!(what_if(&cards, 6, 3).unwrap(), 30);
+        assert!(what_if(&cards, 7, 1).is_err());
+
+        // Wins past the last card are dropped, however many there are.
+        assert_eq!(
+            what_if(&cards, 1, usize::MAX).unwrap(),
+            what_if(&cards, 1, 5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_options_order() {
+        let parse = |args: &[&str]| {
+            Options::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap()
+        };
 
-            let winners = (card.score.ilog2() + 1) as u16;
-            ((id+1)..(id+winners+1)).fold(0, |acc, card| {
-                acc + card_wins(cards, &card, memo)
-            }) + 1
+        for args in [["trace", "--csv"], ["--csv", "trace"]] {
+            let options = parse(&args);
+            assert!(matches!(options.mode, Mode::Trace));
+            assert!(options.csv);
         }
+        assert!(matches!(parse(&["--csv"]).mode, Mode::Trace));
+        assert!(!parse(&["trace"]).csv);
     }
 }
@@ -1,92 +1,647 @@
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 use std::num::ParseIntError;
+use std::ops::Range;
 use std::result::Result;
 
-pub fn seed(f: File) -> Result<(), Box<dyn Error>> {
-    let reader = BufReader::new(f);
+/// One line of a map: values in `src` move by `offset`.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    src: Range<i64>,
+    offset: i64,
+}
 
-    let maps = reader
-        .lines()
-        .map(|l| l.map_err(|e| e.into()))
-        .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
+impl Rule {
+    /// Parses a `<destination start> <source start> <length>` line.
+    fn parse(line: &str) -> Result<Rule, Box<dyn Error>> {
+        let nums = line
+            .split_whitespace()
+            .map(str::parse::<i64>)
+            .collect::<Result<Vec<i64>, ParseIntError>>()
+            .map_err(|e| format!("Invalid map line {}: {}", line, e))?;
 
-    let seed_line = maps.iter().next().ok_or("No seed line")?;
-    let seed_strs = seed_line
-        .split(':')
-        .nth(1)
-        .ok_or("missing RHS of seed line")?;
+        let [dest, src, len] = nums[..] else {
+            return Err(format!("Map line needs 3 numbers: {}", line).into());
+        };
+        if len <= 0 {
+            return Err(format!("Map line has a non-positive length: {}", line).into());
+        }
+        let src_end = src
+            .checked_add(len)
+            .ok_or(format!("Map line overflows i64: {}", line))?;
+        dest.checked_add(len)
+            .ok_or(format!("Map line overflows i64: {}", line))?;
+        let offset = dest
+            .checked_sub(src)
+            .ok_or(format!("Map line overflows i64: {}", line))?;
 
-    let seeds = seed_strs
-        .split_whitespace()
-        .map(str::parse::<i64>)
-        .collect::<Result<Vec<i64>, ParseIntError>>()?;
+        Ok(Rule {
+            src: src..src_end,
+            offset,
+        })
+    }
+}
 
-    let p1 = locate_seeds(&maps[1..], seeds)
-        .and_then(|l| l.iter().min().cloned().ok_or("no location vec".into()))?;
+/// Outcome of looking a value up in one stage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mapping {
+    /// The rule at this index covered the value and moved it.
+    Mapped { rule: usize, value: i64 },
+    /// No rule covered the value, so it carries over unchanged.
+    Unmapped(i64),
+}
 
-    println!("Part 1: {}", p1);
-    Ok(())
+impl Mapping {
+    fn value(&self) -> i64 {
+        match self {
+            Mapping::Mapped { value, .. } => *value,
+            Mapping::Unmapped(value) => *value,
+        }
+    }
+}
+
+/// Prints the rule as its `<destination start> <source start> <length>` map line.
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.src.start + self.offset,
+            self.src.start,
+            self.src.end - self.src.start
+        )
+    }
 }
 
-fn locate_seeds(maps: &[String], mut seeds: Vec<i64>) -> Result<Vec<i64>, Box<dyn Error>> {
-    let mut new_seeds = seeds.clone();
+/// A named map such as `seed-to-soil`, with its rules sorted by source start.
+#[derive(Debug)]
+struct Stage {
+    source: String,
+    destination: String,
+    rules: Vec<Rule>,
+}
+
+impl Stage {
+    /// Parses a `<source>-to-<destination> map:` header and the rule lines following it.
+    /// Overlapping source ranges are rejected, since a value would map two ways.
+    fn parse(header: &str, lines: &[String]) -> Result<Stage, Box<dyn Error>> {
+        let name = header
+            .strip_suffix(" map:")
+            .ok_or(format!("Invalid map header: {}", header))?;
+        let (source, destination) = name.split_once("-to-").ok_or(format!(
+            "Map name should be <source>-to-<destination>: {}",
+            name
+        ))?;
+
+        let mut rules = lines
+            .iter()
+            .map(|l| Rule::parse(l))
+            .collect::<Result<Vec<Rule>, Box<dyn Error>>>()?;
+        rules.sort_by_key(|r| r.src.start);
 
-    for line in maps {
-        if line.is_empty() {
-            continue;
+        for pair in rules.windows(2) {
+            if pair[1].src.start < pair[0].src.end {
+                return Err(format!(
+                    "{}: source ranges {:?} and {:?} overlap",
+                    name, pair[0].src, pair[1].src
+                )
+                .into());
+            }
         }
 
-        if line.ends_with(':') {
-            let mut tmp = seeds;
-            seeds = fill_gaps(&tmp, &new_seeds);
-            tmp.fill(0);
-            new_seeds = tmp;
-            continue;
+        Ok(Stage {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            rules,
+        })
+    }
+
+    /// Maps one value, by binary search over the rules' half-open source ranges.
+    fn lookup(&self, value: i64) -> Mapping {
+        let i = self.rules.partition_point(|r| r.src.start <= value);
+        match i.checked_sub(1) {
+            Some(rule) if self.rules[rule].src.contains(&value) => Mapping::Mapped {
+                rule,
+                value: value + self.rules[rule].offset,
+            },
+            _ => Mapping::Unmapped(value),
         }
+    }
 
-        let nums = line
+    /// Maps whole ranges, splitting each at the rules' source boundaries. Parts covered by
+    /// no rule pass through unchanged.
+    fn map_ranges(&self, ranges: &[Range<i64>]) -> Vec<Range<i64>> {
+        ranges
+            .iter()
+            .flat_map(|range| self.split(range))
+            .map(|(piece, rule)| match rule {
+                Some(i) => piece.start + self.rules[i].offset..piece.end + self.rules[i].offset,
+                None => piece,
+            })
+            .collect()
+    }
+
+    /// Splits `range` at the rules' source boundaries, pairing each piece with the index
+    /// of the rule covering it, if any.
+    fn split(&self, range: &Range<i64>) -> Vec<(Range<i64>, Option<usize>)> {
+        let mut pieces = Vec::new();
+        let mut cursor = range.start;
+
+        let first = self.rules.partition_point(|r| r.src.end <= range.start);
+        for (i, rule) in self.rules.iter().enumerate().skip(first) {
+            if rule.src.start >= range.end {
+                break;
+            }
+            if cursor < rule.src.start {
+                pieces.push((cursor..rule.src.start, None));
+                cursor = rule.src.start;
+            }
+            let end = std::cmp::min(rule.src.end, range.end);
+            pieces.push((cursor..end, Some(i)));
+            cursor = end;
+        }
+        if cursor < range.end {
+            pieces.push((cursor..range.end, None));
+        }
+
+        pieces
+    }
+
+    /// Source ranges whose values map into `range`.
+    fn preimage(&self, range: &Range<i64>) -> Vec<Range<i64>> {
+        let mut sources = self
+            .split(range)
+            .into_iter()
+            .filter(|(_, rule)| rule.is_none())
+            .map(|(piece, _)| piece)
+            .collect::<Vec<Range<i64>>>();
+
+        for rule in &self.rules {
+            let start = std::cmp::max(rule.src.start + rule.offset, range.start);
+            let end = std::cmp::min(rule.src.end + rule.offset, range.end);
+            if start < end {
+                sources.push(start - rule.offset..end - rule.offset);
+            }
+        }
+
+        sources.sort_by_key(|r| r.start);
+        sources
+    }
+}
+
+/// Prints the stage in the almanac's own format.
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}-to-{} map:", self.source, self.destination)?;
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// The seeds and the chain of stages taking a seed to its location.
+#[derive(Debug)]
+struct Almanac {
+    seeds: Vec<i64>,
+    stages: Vec<Stage>,
+}
+
+impl Almanac {
+    fn parse(lines: &[String]) -> Result<Almanac, Box<dyn Error>> {
+        let seed_line = lines.first().ok_or("No seed line")?;
+        let seeds = seed_line
+            .strip_prefix("seeds:")
+            .ok_or("missing seeds: prefix on first line")?
             .split_whitespace()
             .map(str::parse::<i64>)
             .collect::<Result<Vec<i64>, ParseIntError>>()?;
 
-        let dest = nums[0];
-        let src = nums[1];
-        let range = nums[2];
+        let mut stages: Vec<Stage> = Vec::new();
+        let sections = lines[1..].split(|l| l.is_empty()).filter(|s| !s.is_empty());
+        for section in sections {
+            let stage = Stage::parse(&section[0], &section[1..])?;
 
-        for (i, s) in seeds.iter().enumerate() {
-            if src <= *s && *s <= src + range {
-                new_seeds[i] = dest + (s - src)
+            let expected = stages.last().map_or("seed", |s| s.destination.as_str());
+            if stage.source != expected {
+                return Err(format!(
+                    "{}-to-{} map should start from {}",
+                    stage.source, stage.destination, expected
+                )
+                .into());
             }
+            stages.push(stage);
+        }
+
+        Ok(Almanac { seeds, stages })
+    }
+
+    fn locate(&self, seed: i64) -> i64 {
+        self.stages
+            .iter()
+            .fold(seed, |v, stage| stage.lookup(v).value())
+    }
+
+    fn locate_ranges(&self, ranges: Vec<Range<i64>>) -> Vec<Range<i64>> {
+        self.stages
+            .iter()
+            .fold(ranges, |ranges, stage| stage.map_ranges(&ranges))
+    }
+
+    /// Follows `range` through every stage, listing at each category the values it maps
+    /// to and the map line responsible, or `identity` where no line matched. Single values
+    /// print as plain numbers.
+    fn trace(&self, range: Range<i64>) -> String {
+        let show = |r: &Range<i64>| {
+            if r.end - r.start == 1 {
+                r.start.to_string()
+            } else {
+                format!("{}..{}", r.start, r.end)
+            }
+        };
+
+        let source = self.stages.first().map_or("seed", |s| &s.source);
+        let mut report = format!("{}: {}\n", source, show(&range));
+        let mut ranges = vec![range];
+
+        for stage in &self.stages {
+            let mut next = Vec::new();
+            for (piece, rule) in ranges.iter().flat_map(|r| stage.split(r)) {
+                let (mapped, via) = match rule {
+                    Some(i) => {
+                        let rule = &stage.rules[i];
+                        (
+                            piece.start + rule.offset..piece.end + rule.offset,
+                            rule.to_string(),
+                        )
+                    }
+                    None => (piece, "identity".to_string()),
+                };
+                report += &format!("{}: {} ({})\n", stage.destination, show(&mapped), via);
+                next.push(mapped);
+            }
+            ranges = next;
+        }
+
+        report
+    }
+
+    /// Composes every stage into a single stage from the first source to the last
+    /// destination, whose sorted rules form one piecewise-linear map.
+    fn compose(&self) -> Result<Stage, Box<dyn Error>> {
+        let mut pieces = vec![(i64::MIN..i64::MAX, 0)];
+
+        for stage in &self.stages {
+            let mut next = Vec::new();
+            for (src, offset) in pieces {
+                let image = src.start + offset..src.end + offset;
+                for (piece, rule) in stage.split(&image) {
+                    let step = rule.map_or(0, |i| stage.rules[i].offset);
+                    let total = offset.checked_add(step).ok_or(format!(
+                        "Composed offset for seeds {}..{} overflows i64",
+                        piece.start - offset,
+                        piece.end - offset
+                    ))?;
+                    next.push((piece.start - offset..piece.end - offset, total));
+                }
+            }
+            pieces = next;
+            pieces.sort_by_key(|(src, _)| src.start);
+        }
+
+        let mut rules: Vec<Rule> = Vec::new();
+        for (src, offset) in pieces {
+            match rules.last_mut() {
+                Some(last) if last.offset == offset && last.src.end == src.start => {
+                    last.src.end = src.end
+                }
+                _ => rules.push(Rule { src, offset }),
+            }
+        }
+        rules.retain(|r| r.offset != 0);
+
+        Ok(Stage {
+            source: self
+                .stages
+                .first()
+                .map_or("seed", |s| &s.source)
+                .to_string(),
+            destination: self
+                .stages
+                .last()
+                .map_or("seed", |s| &s.destination)
+                .to_string(),
+            rules,
+        })
+    }
+
+    /// Reads the seeds as (start, length) pairs of half-open ranges.
+    fn seed_ranges(&self) -> Result<Vec<Range<i64>>, Box<dyn Error>> {
+        if self.seeds.len() % 2 != 0 {
+            return Err("seed line must hold (start, length) pairs for part 2".into());
         }
+
+        self.seeds
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                range_from(pair[0], pair[1]).map_err(|e| {
+                    format!("Seed pair {} ({} {}): {}", i + 1, pair[0], pair[1], e).into()
+                })
+            })
+            .collect()
     }
+}
 
-    Ok(fill_gaps(&seeds, &new_seeds))
+/// The half-open range of `len > 0` values starting at `start`.
+fn range_from(start: i64, len: i64) -> Result<Range<i64>, Box<dyn Error>> {
+    if len <= 0 {
+        return Err(format!("Range {} + {} has a non-positive length", start, len).into());
+    }
+    let end = start
+        .checked_add(len)
+        .ok_or(format!("Range {} + {} overflows i64", start, len))?;
+    Ok(start..end)
 }
 
-fn fill_gaps(old: &Vec<i64>, new: &Vec<i64>) -> Vec<i64> {
-    return new
+pub fn seed(f: File, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let reader = BufReader::new(f);
+
+    let lines = reader
+        .lines()
+        .map(|l| l.map_err(|e| e.into()))
+        .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
+
+    let almanac = Almanac::parse(&lines)?;
+
+    match args {
+        [] => (),
+        [mode] if mode == "compose" => {
+            print!("{}", almanac.compose()?);
+            return Ok(());
+        }
+        [mode, start, end] if mode == "reverse" => {
+            let range = start.parse::<i64>()?..end.parse::<i64>()?;
+            for r in almanac.compose()?.preimage(&range) {
+                println!("{}..{}", r.start, r.end);
+            }
+            return Ok(());
+        }
+        [mode, seed] if mode == "trace" => {
+            let seed = seed.parse::<i64>()?;
+            print!("{}", almanac.trace(range_from(seed, 1)?));
+            return Ok(());
+        }
+        [mode, start, len] if mode == "trace" => {
+            let start = start.parse::<i64>()?;
+            print!("{}", almanac.trace(range_from(start, len.parse::<i64>()?)?));
+            return Ok(());
+        }
+        _ => {
+            return Err("Usage: [compose | reverse <start> <end> | trace <seed> [<length>]]".into())
+        }
+    }
+
+    let p1 = almanac
+        .seeds
+        .iter()
+        .map(|s| almanac.locate(*s))
+        .min()
+        .ok_or("no seeds")?;
+
+    let p2 = almanac
+        .locate_ranges(almanac.seed_ranges()?)
         .iter()
-        .enumerate()
-        .map(|(i, s)| if *s == 0 { old[i] } else { *s })
-        .collect();
+        .map(|r| r.start)
+        .min()
+        .ok_or("no location ranges")?;
+
+    println!("Part 1: {}", p1);
+    println!("Part 2: {}", p2);
+    Ok(())
 }
 
 mod tests {
     #[cfg(test)]
     use super::*;
 
+    #[cfg(test)]
+    const EXAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    #[cfg(test)]
+    fn parse(text: &str) -> Result<Almanac, Box<dyn Error>> {
+        Almanac::parse(&text.lines().map(String::from).collect::<Vec<String>>())
+    }
+
     #[test]
     fn test_locate_seeds() {
-        let maps = vec![
-            "seed-to-soil map:".to_string(),
-            "50 98 2".to_string(),
-            "52 50 48".to_string(),
-            "".to_string(),
-        ];
-        let seeds = vec![79, 14, 55, 13];
-        let actual = locate_seeds(&maps, seeds).unwrap();
-        assert_eq!(vec![81, 14, 57, 13], actual);
+        let almanac = parse(EXAMPLE).unwrap();
+        let soil = almanac
+            .seeds
+            .iter()
+            .map(|s| almanac.stages[0].lookup(*s).value());
+        assert_eq!(vec![81, 14, 57, 13], soil.collect::<Vec<i64>>());
+
+        let locations = almanac.seeds.iter().map(|s| almanac.locate(*s));
+        assert_eq!(vec![82, 43, 86, 35], locations.collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn test_locate_ranges() {
+        let almanac = parse(EXAMPLE).unwrap();
+        let located = almanac.locate_ranges(almanac.seed_ranges().unwrap());
+        assert_eq!(located.iter().map(|r| r.start).min(), Some(46));
+        assert_eq!(located.iter().map(|r| r.end - r.start).sum::<i64>(), 27);
+    }
+
+    #[test]
+    fn test_almanac_validation() {
+        let err = parse("seeds: 1\n\nseed-to-soil map:\n1 2 3\n\nwater-to-light map:\n1 2 3")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "water-to-light map should start from soil");
+
+        let err = parse("seeds: 1\n\nseed-to-soil map:\n0 10 5\n20 14 3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "seed-to-soil: source ranges 10..15 and 14..17 overlap"
+        );
+
+        let err = parse("seeds: 1\n\nseed-to-soil map:\n0 10").unwrap_err();
+        assert_eq!(err.to_string(), "Map line needs 3 numbers: 0 10");
+
+        let err = parse("seeds: 1\n\nseed-to-soil:\n0 10 1").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid map header: seed-to-soil:");
+    }
+
+    #[test]
+    fn test_range_end_is_exclusive() {
+        let almanac = parse("seeds: 97 4\n\nseed-to-soil map:\n50 98 2").unwrap();
+        let stage = &almanac.stages[0];
+        assert_eq!(stage.lookup(97), Mapping::Unmapped(97));
+        assert_eq!(stage.lookup(98), Mapping::Mapped { rule: 0, value: 50 });
+        assert_eq!(stage.lookup(99), Mapping::Mapped { rule: 0, value: 51 });
+        assert_eq!(stage.lookup(100), Mapping::Unmapped(100));
+        let seeds = almanac.seed_ranges().unwrap();
+        assert_eq!(stage.map_ranges(&seeds), [97..98, 50..52, 100..101]);
+    }
+
+    #[test]
+    fn test_location_zero() {
+        let almanac =
+            parse("seeds: 5 2\n\nseed-to-soil map:\n0 5 1\n\nsoil-to-location map:\n100 200 5")
+                .unwrap();
+        assert_eq!(
+            almanac.stages[0].lookup(5),
+            Mapping::Mapped { rule: 0, value: 0 }
+        );
+        assert_eq!(almanac.stages[1].lookup(0), Mapping::Unmapped(0));
+        assert_eq!(almanac.locate(5), 0);
+        assert_eq!(almanac.locate(6), 6);
+        let seeds = almanac.seed_ranges().unwrap();
+        assert_eq!(almanac.locate_ranges(seeds), [0..1, 6..7]);
+    }
+
+    #[test]
+    fn test_compose() {
+        let almanac = parse(EXAMPLE).unwrap();
+        let composed = almanac.compose().unwrap();
+        assert_eq!(
+            (composed.source.as_str(), composed.destination.as_str()),
+            ("seed", "location")
+        );
+
+        for seed in -5..120 {
+            assert_eq!(composed.lookup(seed).value(), almanac.locate(seed));
+        }
+        let seeds = almanac.seed_ranges().unwrap();
+        let mut expected = almanac.locate_ranges(seeds.clone());
+        let mut actual = composed.map_ranges(&seeds);
+        expected.sort_by_key(|r| r.start);
+        actual.sort_by_key(|r| r.start);
+        assert_eq!(actual, expected);
+
+        let printed = composed.to_string();
+        let reparsed = Stage::parse(
+            printed.lines().next().unwrap(),
+            &printed
+                .lines()
+                .skip(1)
+                .map(String::from)
+                .collect::<Vec<String>>(),
+        )
+        .unwrap();
+        assert_eq!(reparsed.rules, composed.rules);
+    }
+
+    #[test]
+    fn test_offset_overflow() {
+        assert!(Rule::parse("9223372036854775000 -9223372036854775000 5").is_err());
+
+        // Each offset fits, but together they move a seed further than i64 allows.
+        let almanac = parse(
+            "seeds: 1\n\nseed-to-soil map:\n0 -9000000000000000000 10\n\n\
+             soil-to-location map:\n9000000000000000000 0 10",
+        )
+        .unwrap();
+        assert!(almanac.compose().is_err());
+    }
+
+    #[test]
+    fn test_preimage() {
+        let almanac = parse(EXAMPLE).unwrap();
+        let composed = almanac.compose().unwrap();
+
+        let seeds = composed.preimage(&(40..50));
+        for seed in -5..120 {
+            let lands = (40..50).contains(&almanac.locate(seed));
+            assert_eq!(
+                seeds.iter().any(|r| r.contains(&seed)),
+                lands,
+                "seed {}",
+                seed
+            );
+        }
+        assert!(seeds.iter().any(|r| r.contains(&82)));
+    }
+
+    #[test]
+    fn test_trace() {
+        let almanac = parse(EXAMPLE).unwrap();
+        let expected = "\
+seed: 79
+soil: 81 (52 50 48)
+fertilizer: 81 (identity)
+water: 81 (identity)
+light: 74 (18 25 70)
+temperature: 78 (68 64 13)
+humidity: 78 (identity)
+location: 82 (60 56 37)
+";
+        assert_eq!(almanac.trace(79..80), expected);
+
+        let expected = "\
+seed: 97..100
+soil: 97 (identity)
+soil: 50..52 (50 98 2)
+";
+        let almanac = parse("seeds: 97 3\n\nseed-to-soil map:\n50 98 2").unwrap();
+        assert_eq!(almanac.trace(97..100), expected);
+    }
+
+    #[test]
+    fn test_range_from() {
+        assert_eq!(range_from(79, 1).unwrap(), 79..80);
+        assert!(range_from(i64::MAX, 1).is_err());
+        assert!(range_from(i64::MAX - 5, 10).is_err());
+        assert!(range_from(5, 0).is_err());
+        assert!(range_from(5, -3).is_err());
+
+        let almanac = parse(&format!(
+            "seeds: {} 1\n\nseed-to-soil map:\n0 0 1",
+            i64::MAX
+        ))
+        .unwrap();
+        assert!(almanac.seed_ranges().is_err());
+
+        let almanac = parse("seeds: 79 14 55 -13\n\nseed-to-soil map:\n0 0 1").unwrap();
+        assert_eq!(
+            almanac.seed_ranges().unwrap_err().to_string(),
+            "Seed pair 2 (55 -13): Range 55 + -13 has a non-positive length"
+        );
     }
 }
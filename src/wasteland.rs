@@ -36,7 +36,7 @@ impl Node {
     }
 
     fn parse_into_map(
-        line: &String,
+        line: &str,
         nodes: &mut HashMap<String, Rc<RefCell<Node>>>,
     ) -> Result<(), Box<dyn Error>> {
         let parts: Vec<&str> = line
@@ -69,7 +69,7 @@ impl Node {
 fn count_steps(
     node: &Rc<RefCell<Node>>,
     dest_id: &str,
-    steps: &Vec<char>,
+    steps: &[char],
 ) -> Result<usize, Box<dyn Error>> {
     let mut cur = node.clone();
     let mut step: usize = 0;
@@ -84,7 +84,7 @@ fn count_steps(
 
 fn count_ghost_steps(
     nodes: Vec<Rc<RefCell<Node>>>,
-    steps: &Vec<char>,
+    steps: &[char],
 ) -> Result<usize, Box<dyn Error>> {
     nodes
         .iter()
@@ -94,7 +94,7 @@ fn count_ghost_steps(
 
 fn steps_to_ghost_end(
     node: &Rc<RefCell<Node>>,
-    steps: &Vec<char>,
+    steps: &[char],
 ) -> Result<usize, Box<dyn Error>> {
     let mut cur = node.clone();
     let mut step: usize = 0;
@@ -148,7 +148,7 @@ pub fn wasteland(f: File) -> Result<(), Box<dyn Error>> {
     let p2_nodes: Vec<Rc<RefCell<Node>>> = map
         .values()
         .filter(|n| n.borrow().id.ends_with('A'))
-        .map(|n| n.clone())
+        .cloned()
         .collect();
     let p2 = count_ghost_steps(p2_nodes, &steps)?;
 